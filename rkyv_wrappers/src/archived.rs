@@ -0,0 +1,18 @@
+//! Conversions from archived primitives used by the wrappers.
+
+use rkyv::{Archive, Archived};
+
+/// Converts an archived primitive to its native type.
+///
+/// `Archived<T>` is only `T` when archiving with the native endianness, so converting it directly
+/// looks useless to clippy.
+#[inline]
+pub(crate) fn native<T: Archive + From<Archived<T>>>(value: Archived<T>) -> T {
+    T::from(value)
+}
+
+/// Converts an archived `u32` length or index to a `usize`.
+#[inline]
+pub(crate) fn to_usize(value: Archived<u32>) -> usize {
+    native::<u32>(value) as usize
+}
@@ -0,0 +1,411 @@
+//! A wrapper that packs a `Vec` of booleans into bits at serialization time.
+
+use crate::{
+    archived::{native, to_usize},
+    as_hashmap::EntryCountError,
+};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible,
+};
+use std::{fmt, iter::FusedIterator};
+
+/// A wrapper that packs a vector or array of booleans into an [`ArchivedBitVec`].
+///
+/// Booleans are archived as one byte each. `AsBitVec` packs them into `u64` words instead, which
+/// takes up an eighth of the space. Bit `i` is stored in bit `i % 64` of word `i / 64`, and the
+/// unused bits of the last word are always zero.
+///
+/// `AsBitVec` works with `Vec<bool>` and `[bool; N]`. Deserializing into an array returns an
+/// [`EntryCountError`] if the archived vector doesn't have exactly `N` booleans, so the
+/// deserializer's error type must implement `From<EntryCountError>`.
+///
+/// With the `validation` feature, checking an `ArchivedBitVec` verifies that it has the right
+/// number of words for its length and that the unused bits of the last word are zero.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Fallible};
+/// use rkyv_wrappers::as_bitvec::AsBitVec;
+/// use std::error::Error;
+///
+/// // A deserializer whose error type can hold an `EntryCountError`
+/// struct MyDeserializer;
+/// impl Fallible for MyDeserializer {
+///     type Error = Box<dyn Error>;
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithFlags {
+///     #[with(AsBitVec)]
+///     pub flags: Vec<bool>,
+///     #[with(AsBitVec)]
+///     pub mask: [bool; 4],
+/// }
+/// let original = StructWithFlags {
+///     flags: (0..100).map(|i| i % 3 == 0).collect(),
+///     mask: [true, false, false, true],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithFlags>(&buffer)
+/// };
+/// assert_eq!(output.flags.len(), 100);
+/// assert_eq!(output.flags.get(3), Some(true));
+/// assert_eq!(output.flags.get(4), Some(false));
+/// assert_eq!(output.flags.count_ones(), 34);
+/// assert!(output.mask.iter().eq([true, false, false, true].iter().copied()));
+/// let deserialized: StructWithFlags = output.deserialize(&mut MyDeserializer).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsBitVec;
+
+/// The number of bits in each word of an [`ArchivedBitVec`].
+const WORD_BITS: usize = 64;
+
+/// An archived vector of booleans packed into bits.
+///
+/// This is the archived version of vectors and arrays labeled [`AsBitVec`].
+#[repr(C)]
+pub struct ArchivedBitVec {
+    len: Archived<u32>,
+    words: ArchivedVec<Archived<u64>>,
+}
+
+impl ArchivedBitVec {
+    /// Gets the number of booleans in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        to_usize(self.len)
+    }
+
+    /// Returns whether the vector contains no booleans.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the boolean at the given position.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<bool> {
+        if index < self.len() {
+            let word = native::<u64>(self.words[index / WORD_BITS]);
+            Some((word >> (index % WORD_BITS)) & 1 == 1)
+        } else {
+            None
+        }
+    }
+
+    /// Counts the number of `true` booleans in the vector.
+    #[inline]
+    pub fn count_ones(&self) -> usize {
+        self.words
+            .iter()
+            .map(|&word| native::<u64>(word).count_ones() as usize)
+            .sum()
+    }
+
+    /// Counts the number of `false` booleans in the vector.
+    #[inline]
+    pub fn count_zeros(&self) -> usize {
+        self.len() - self.count_ones()
+    }
+
+    /// Gets the words that the booleans are packed into.
+    #[inline]
+    pub fn as_words(&self) -> &[Archived<u64>] {
+        self.words.as_slice()
+    }
+
+    /// Gets an iterator over the booleans in the vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            bits: self,
+            start: 0,
+            end: self.len(),
+        }
+    }
+}
+
+impl fmt::Debug for ArchivedBitVec {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a ArchivedBitVec {
+    type Item = bool;
+    type IntoIter = Iter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the booleans of an [`ArchivedBitVec`].
+pub struct Iter<'a> {
+    bits: &'a ArchivedBitVec,
+    start: usize,
+    end: usize,
+}
+
+impl Iterator for Iter<'_> {
+    type Item = bool;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.start < self.end {
+            let result = self.bits.get(self.start);
+            self.start += 1;
+            result
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.end - self.start;
+        (remaining, Some(remaining))
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.start < self.end {
+            self.end -= 1;
+            self.bits.get(self.end)
+        } else {
+            None
+        }
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+/// Errors that can occur while checking an [`ArchivedBitVec`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum BitVecError<W> {
+    /// An error occurred while checking the words
+    WordsError(W),
+    /// The number of words does not match the length
+    InvalidLength {
+        /// The number of booleans
+        len: usize,
+        /// The number of words
+        words: usize,
+    },
+    /// One of the unused bits of the last word is set
+    TrailingBits,
+}
+
+#[cfg(feature = "validation")]
+impl<W: fmt::Display> fmt::Display for BitVecError<W> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BitVecError::WordsError(e) => write!(f, "words check error: {}", e),
+            BitVecError::InvalidLength { len, words } => write!(
+                f,
+                "invalid length: {} words cannot hold exactly {} bits",
+                words, len
+            ),
+            BitVecError::TrailingBits => write!(f, "trailing bits set after the last bit"),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<W: std::error::Error + 'static> std::error::Error for BitVecError<W> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            BitVecError::WordsError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<C> CheckBytes<C> for ArchivedBitVec
+    where
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = BitVecError<<ArchivedVec<Archived<u64>> as CheckBytes<C>>::Error>;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let words = ArchivedVec::check_bytes(ptr::addr_of!((*value).words), context)
+                .map_err(BitVecError::WordsError)?;
+            let value = &*value;
+
+            let len = value.len();
+            // The length comes from the archive, so rounding it up could overflow
+            if words.len() != len / WORD_BITS + (len % WORD_BITS != 0) as usize {
+                return Err(BitVecError::InvalidLength {
+                    len,
+                    words: words.len(),
+                });
+            }
+            let used = len % WORD_BITS;
+            if let Some(&last) = words.last() {
+                if used != 0 && native::<u64>(last) >> used != 0 {
+                    return Err(BitVecError::TrailingBits);
+                }
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsBitVec`].
+pub struct BitVecResolver {
+    words: VecResolver,
+}
+
+/// Packs up to 64 booleans into a word.
+#[inline]
+fn pack(bits: &[bool]) -> u64 {
+    bits.iter()
+        .enumerate()
+        .fold(0, |word, (i, &bit)| word | ((bit as u64) << i))
+}
+
+/// Resolves the archived bits of `bits`.
+#[inline]
+unsafe fn resolve_bits(
+    bits: &[bool],
+    pos: usize,
+    resolver: BitVecResolver,
+    out: *mut ArchivedBitVec,
+) {
+    let (fp, fo) = out_field!(out.len);
+    (bits.len() as u32).resolve(pos + fp, (), fo);
+    let (fp, fo) = out_field!(out.words);
+    ArchivedVec::resolve_from_len(
+        (bits.len() + WORD_BITS - 1) / WORD_BITS,
+        pos + fp,
+        resolver.words,
+        fo,
+    );
+}
+
+/// Serializes the words of `bits`.
+///
+/// # Panics
+///
+/// Panics if there are more than `u32::MAX` booleans.
+#[inline]
+fn serialize_bits<S: ScratchSpace + Serializer + ?Sized>(
+    bits: &[bool],
+    serializer: &mut S,
+) -> Result<BitVecResolver, S::Error> {
+    assert!(
+        bits.len() <= u32::MAX as usize,
+        "AsBitVec supports at most u32::MAX booleans"
+    );
+
+    Ok(BitVecResolver {
+        words: ArchivedVec::serialize_from_iter::<u64, _, _, _>(
+            bits.chunks(WORD_BITS).map(pack),
+            serializer,
+        )?,
+    })
+}
+
+impl ArchiveWith<Vec<bool>> for AsBitVec {
+    type Archived = ArchivedBitVec;
+    type Resolver = BitVecResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<bool>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        resolve_bits(field, pos, resolver, out);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` booleans.
+impl<S: ScratchSpace + Serializer + Fallible + ?Sized> SerializeWith<Vec<bool>, S> for AsBitVec {
+    #[inline]
+    fn serialize_with(field: &Vec<bool>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        serialize_bits(field, serializer)
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<ArchivedBitVec, Vec<bool>, D> for AsBitVec {
+    #[inline]
+    fn deserialize_with(field: &ArchivedBitVec, _: &mut D) -> Result<Vec<bool>, D::Error> {
+        Ok(field.iter().collect())
+    }
+}
+
+impl<const N: usize> ArchiveWith<[bool; N]> for AsBitVec {
+    type Archived = ArchivedBitVec;
+    type Resolver = BitVecResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &[bool; N],
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        resolve_bits(field, pos, resolver, out);
+    }
+}
+
+/// # Panics
+///
+/// Panics if `N` is larger than `u32::MAX`.
+impl<S: ScratchSpace + Serializer + Fallible + ?Sized, const N: usize> SerializeWith<[bool; N], S>
+    for AsBitVec
+{
+    #[inline]
+    fn serialize_with(field: &[bool; N], serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        serialize_bits(field, serializer)
+    }
+}
+
+impl<D, const N: usize> DeserializeWith<ArchivedBitVec, [bool; N], D> for AsBitVec
+where
+    D: Fallible + ?Sized,
+    D::Error: From<EntryCountError>,
+{
+    #[inline]
+    fn deserialize_with(field: &ArchivedBitVec, _: &mut D) -> Result<[bool; N], D::Error> {
+        if field.len() != N {
+            return Err(EntryCountError {
+                expected: N,
+                found: field.len(),
+            }
+            .into());
+        }
+
+        let mut result = [false; N];
+        for (bit, value) in result.iter_mut().zip(field.iter()) {
+            *bit = value;
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,129 @@
+//! A wrapper that converts a `Vec` to an `ArchivedBTreeMap` at serialization time.
+
+use crate::as_hashmap::{sorted_entries, DuplicateKeyPolicy, LastWins};
+use rkyv::{
+    collections::btree_map::{ArchivedBTreeMap, BTreeMapResolver},
+    ser::{ScratchSpace, Serializer},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Deserialize, Fallible, Serialize,
+};
+use std::marker::PhantomData;
+
+/// A wrapper that attempts to convert a vector to and from `ArchivedBTreeMap`
+///
+/// Building a `BTreeMap` only to archive it allocates and links every node of the tree, none of
+/// which are used by the archived version. `AsBTreeMap` instead sorts the indices of the entries in
+/// the serializer's scratch space and writes the archived tree directly. If the vector is already
+/// sorted by key with no repeated keys, sorting is skipped entirely.
+///
+/// Entries that repeat a key are handled by the [`DuplicateKeyPolicy`] `P`. The default,
+/// [`LastWins`], matches collecting the vector into a `BTreeMap`.
+///
+/// Deserializing produces a vector with one entry per unique key, sorted by key.
+///
+/// With the `validation` feature, the archived map is checked like any other `ArchivedBTreeMap`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithBTreeMap {
+///     #[with(rkyv_wrappers::as_btreemap::AsBTreeMap)]
+///     pub btree_map: Vec<(u32, String)>,
+/// }
+/// let original = StructWithBTreeMap {
+///     btree_map: vec![(2, String::from("b")), (1, String::from("a"))]
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithBTreeMap>(&buffer)
+/// };
+/// assert_eq!(output.btree_map.get(&1).unwrap(), &"a");
+/// assert_eq!(output.btree_map.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+/// let deserialized: StructWithBTreeMap = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.btree_map, vec![(1, String::from("a")), (2, String::from("b"))]);
+/// ```
+pub struct AsBTreeMap<P = LastWins> {
+    _policy: PhantomData<P>,
+}
+
+/// The resolver for [`AsBTreeMap`].
+pub struct AsBTreeMapResolver {
+    len: usize,
+    inner: BTreeMapResolver,
+}
+
+impl<K: Archive, V: Archive, P> ArchiveWith<Vec<(K, V)>> for AsBTreeMap<P> {
+    type Archived = ArchivedBTreeMap<K::Archived, V::Archived>;
+    type Resolver = AsBTreeMapResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        _: &Vec<(K, V)>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        ArchivedBTreeMap::resolve_from_len(resolver.len, pos, resolver.inner, out);
+    }
+}
+
+impl<
+        K: Archive + Serialize<S> + Ord,
+        V: Archive + Serialize<S>,
+        S: ScratchSpace + Serializer + Fallible + ?Sized,
+        P: DuplicateKeyPolicy<S::Error>,
+    > SerializeWith<Vec<(K, V)>, S> for AsBTreeMap<P>
+{
+    #[inline]
+    fn serialize_with(field: &Vec<(K, V)>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        if field.windows(2).all(|w| w[0].0 < w[1].0) {
+            // Already sorted with unique keys
+            let inner = unsafe {
+                ArchivedBTreeMap::serialize_from_reverse_iter(
+                    field.iter().rev().map(|(k, v)| (k, v)),
+                    serializer,
+                )?
+            };
+            return Ok(AsBTreeMapResolver {
+                len: field.len(),
+                inner,
+            });
+        }
+
+        unsafe {
+            let entries =
+                sorted_entries(field.len(), |i| &field[i].0, serializer, P::on_duplicate)?;
+            let inner = ArchivedBTreeMap::serialize_from_reverse_iter(
+                entries
+                    .iter()
+                    .rev()
+                    .map(|&(k, v)| (&field[k].0, &field[v].1)),
+                serializer,
+            )?;
+            let len = entries.len();
+            entries.free(serializer)?;
+
+            Ok(AsBTreeMapResolver { len, inner })
+        }
+    }
+}
+
+impl<K: Archive, V: Archive, D: Fallible + ?Sized, P>
+    DeserializeWith<ArchivedBTreeMap<K::Archived, V::Archived>, Vec<(K, V)>, D> for AsBTreeMap<P>
+where
+    K::Archived: Deserialize<K, D>,
+    V::Archived: Deserialize<V, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedBTreeMap<K::Archived, V::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<(K, V)>, D::Error> {
+        field
+            .iter()
+            .map(|(k, v)| Ok((k.deserialize(deserializer)?, v.deserialize(deserializer)?)))
+            .collect()
+    }
+}
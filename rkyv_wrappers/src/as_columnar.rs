@@ -0,0 +1,189 @@
+//! A wrapper that archives a `Vec` of structs as one archived vector per field.
+
+use rkyv::{
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Fallible,
+};
+
+#[cfg(feature = "derive")]
+pub use rkyv_wrappers_derive::Columnar;
+
+/// A type whose vectors can be archived as columns with [`AsColumnar`].
+///
+/// This should be derived with `#[derive(Columnar)]` from the `derive` feature rather than
+/// implemented by hand.
+pub trait Columnar: Sized {
+    /// The archived columns of a vector of this type.
+    type Columns;
+    /// The resolver for the archived columns.
+    type Resolver;
+
+    /// Creates the archived columns for `rows` at the given position and writes them to the given
+    /// output.
+    ///
+    /// # Safety
+    ///
+    /// - `pos` must be the position of `out` within the archive
+    /// - `resolver` must be the result of serializing `rows`
+    unsafe fn resolve_columns(
+        rows: &[Self],
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Columns,
+    );
+}
+
+/// A [`Columnar`] type whose columns can be serialized with the serializer `S`.
+pub trait SerializeColumnar<S: Fallible + ?Sized>: Columnar {
+    /// Writes the column of each field of `rows` to the serializer.
+    fn serialize_columns(rows: &[Self], serializer: &mut S) -> Result<Self::Resolver, S::Error>;
+}
+
+/// A [`Columnar`] type whose rows can be deserialized from archived columns with the deserializer
+/// `D`.
+pub trait DeserializeColumnar<D: Fallible + ?Sized>: Columnar {
+    /// Deserializes the rows of `columns`.
+    fn deserialize_columns(
+        columns: &Self::Columns,
+        deserializer: &mut D,
+    ) -> Result<Vec<Self>, D::Error>;
+}
+
+/// A wrapper that converts a vector of structs to and from one archived vector per field.
+///
+/// Scanning a single field of an archived `Vec` of structs reads every field of every struct.
+/// `AsColumnar` stores the values of each field contiguously instead, so a scan over one field
+/// only touches that field's column. The archived columns also provide a view of each row that
+/// borrows one value from every column.
+///
+/// The struct must derive `Columnar`, which generates the archived columns type. The derive macro
+/// requires the `derive` feature and is documented in
+/// [`rkyv_wrappers_derive`](https://docs.rs/rkyv_wrappers_derive).
+///
+/// With the `validation` feature and `#[columnar(check_bytes)]`, checking the archived columns
+/// checks every column and verifies that all columns have the same length.
+///
+/// Example:
+///
+#[cfg_attr(feature = "derive", doc = "```rust")]
+#[cfg_attr(not(feature = "derive"), doc = "```ignore")]
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_columnar::{AsColumnar, Columnar};
+///
+/// #[derive(Columnar, Debug, PartialEq)]
+/// struct Trade {
+///     price: f64,
+///     quantity: u32,
+///     symbol: String,
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct Trades {
+///     #[with(AsColumnar)]
+///     pub trades: Vec<Trade>,
+/// }
+///
+/// let original = Trades {
+///     trades: vec![
+///         Trade { price: 10.5, quantity: 100, symbol: String::from("ABC") },
+///         Trade { price: 11.0, quantity: 20, symbol: String::from("XYZ") },
+///         Trade { price: 10.75, quantity: 5, symbol: String::from("ABC") },
+///     ],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<Trades>(&buffer)
+/// };
+/// assert_eq!(output.trades.len(), 3);
+/// assert_eq!(output.trades.quantity.iter().sum::<u32>(), 125);
+/// let row = output.trades.row(1).unwrap();
+/// assert_eq!(*row.price, 11.0);
+/// assert_eq!(row.symbol, "XYZ");
+/// assert_eq!(output.trades.rows().filter(|row| row.symbol == "ABC").count(), 2);
+/// let deserialized: Trades = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsColumnar;
+
+/// Errors that can occur while checking archived columns.
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum ColumnarError {
+    /// An error occurred while checking a column
+    ColumnError {
+        /// The name of the column
+        column: &'static str,
+        /// The error that occurred
+        error: Box<dyn std::error::Error>,
+    },
+    /// A column has a different length than the first column
+    LengthMismatch {
+        /// The name of the column
+        column: &'static str,
+        /// The length of the first column
+        expected: usize,
+        /// The length of the column
+        actual: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl std::fmt::Display for ColumnarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ColumnarError::ColumnError { column, error } => {
+                write!(f, "check error in column `{}`: {}", column, error)
+            }
+            ColumnarError::LengthMismatch {
+                column,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "length mismatch: column `{}` has {} rows but expected {}",
+                column, actual, expected
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl std::error::Error for ColumnarError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ColumnarError::ColumnError { error, .. } => Some(error.as_ref()),
+            ColumnarError::LengthMismatch { .. } => None,
+        }
+    }
+}
+
+impl<T: Columnar> ArchiveWith<Vec<T>> for AsColumnar {
+    type Archived = T::Columns;
+    type Resolver = T::Resolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        T::resolve_columns(field, pos, resolver, out);
+    }
+}
+
+impl<T: SerializeColumnar<S>, S: Fallible + ?Sized> SerializeWith<Vec<T>, S> for AsColumnar {
+    #[inline]
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        T::serialize_columns(field, serializer)
+    }
+}
+
+impl<T: DeserializeColumnar<D>, D: Fallible + ?Sized> DeserializeWith<T::Columns, Vec<T>, D>
+    for AsColumnar
+{
+    #[inline]
+    fn deserialize_with(field: &T::Columns, deserializer: &mut D) -> Result<Vec<T>, D::Error> {
+        T::deserialize_columns(field, deserializer)
+    }
+}
@@ -0,0 +1,220 @@
+//! Wrappers that archive a field as another type by converting it at serialization time.
+
+use rkyv::{
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, Serialize,
+};
+use std::{convert::TryFrom, marker::PhantomData};
+
+/// A wrapper that archives a field as the archived version of `I` by converting it with [`From`].
+///
+/// Serializing converts a reference to the field into an `I` with `From<&T>` and serializes that
+/// instead, so the field archives as `Archived<I>`. Deserializing deserializes the `I` and converts
+/// it back into the field with `From<I>`. This changes the archived representation of a field with
+/// a pair of `From` implementations instead of a custom wrapper. Conversions that can fail should
+/// use [`AsTryConvert`] instead.
+///
+/// With the `validation` feature, checking the archived field is the same as checking an
+/// `Archived<I>`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_convert::AsConvert;
+///
+/// #[derive(Debug, PartialEq)]
+/// struct Rgb {
+///     r: u8,
+///     g: u8,
+///     b: u8,
+/// }
+/// impl From<&Rgb> for u32 {
+///     fn from(color: &Rgb) -> Self {
+///         u32::from_be_bytes([0, color.r, color.g, color.b])
+///     }
+/// }
+/// impl From<u32> for Rgb {
+///     fn from(color: u32) -> Self {
+///         let [_, r, g, b] = color.to_be_bytes();
+///         Rgb { r, g, b }
+///     }
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct Pixel {
+///     #[with(AsConvert<u32>)]
+///     pub color: Rgb,
+/// }
+/// let original = Pixel {
+///     color: Rgb { r: 0xff, g: 0x80, b: 0x00 },
+/// };
+/// let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<Pixel>(&buffer)
+/// };
+/// assert_eq!(output.color, 0xff8000);
+/// let deserialized: Pixel = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsConvert<I> {
+    _phantom: PhantomData<I>,
+}
+
+/// A wrapper that archives a field as the archived version of `I` by converting it with
+/// [`TryFrom`].
+///
+/// Serializing converts a reference to the field into an `I` with `TryFrom<&T>` and serializes that
+/// instead, so the field archives as `Archived<I>`. Deserializing deserializes the `I` and converts
+/// it back into the field with `TryFrom<I>`. Conversion errors are returned as serializer and
+/// deserializer errors, so their error types must implement `From` for the conversion errors (see
+/// [Serializer errors](crate#serializer-errors)).
+/// Converting back with a `From` implementation can't fail, so it works with rkyv's `Infallible`
+/// deserializer.
+///
+/// With the `validation` feature, checking the archived field is the same as checking an
+/// `Archived<I>`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+/// use rkyv_wrappers::{as_convert::AsTryConvert, BoxedErrorSerializer};
+/// use std::{convert::TryFrom, num::TryFromIntError};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct Port(u16);
+/// impl TryFrom<&u32> for Port {
+///     type Error = TryFromIntError;
+///     fn try_from(port: &u32) -> Result<Self, Self::Error> {
+///         u16::try_from(*port).map(Port)
+///     }
+/// }
+/// impl From<Port> for u32 {
+///     fn from(port: Port) -> Self {
+///         port.0.into()
+///     }
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct Config {
+///     #[with(AsTryConvert<Port>)]
+///     pub port: u32,
+/// }
+/// let original = Config { port: 8080 };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_inner();
+/// let output = unsafe {
+///     archived_root::<Config>(&buffer)
+/// };
+/// assert_eq!(output.port.0, 8080);
+/// let deserialized: Config = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+///
+/// let mut serializer = BoxedErrorSerializer::default();
+/// let error = serializer.serialize_value(&Config { port: 65536 }).unwrap_err();
+/// assert!(error.downcast_ref::<TryFromIntError>().is_some());
+/// ```
+pub struct AsTryConvert<I> {
+    _phantom: PhantomData<I>,
+}
+
+/// The resolver for [`AsConvert`] and [`AsTryConvert`].
+///
+/// It holds on to the converted value so that the field only has to be converted once.
+pub struct ConvertResolver<I: Archive> {
+    value: I,
+    resolver: I::Resolver,
+}
+
+impl<I: Archive> ConvertResolver<I> {
+    /// Serializes a converted value.
+    #[inline]
+    fn serialize<S>(value: I, serializer: &mut S) -> Result<Self, S::Error>
+    where
+        I: Serialize<S>,
+        S: Fallible + ?Sized,
+    {
+        let resolver = value.serialize(serializer)?;
+        Ok(Self { value, resolver })
+    }
+
+    /// Resolves the converted value.
+    #[inline]
+    unsafe fn resolve(self, pos: usize, out: *mut Archived<I>) {
+        self.value.resolve(pos, self.resolver, out);
+    }
+}
+
+impl<T, I: Archive> ArchiveWith<T> for AsConvert<I> {
+    type Archived = Archived<I>;
+    type Resolver = ConvertResolver<I>;
+
+    #[inline]
+    unsafe fn resolve_with(_: &T, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        resolver.resolve(pos, out);
+    }
+}
+
+impl<T, I, S> SerializeWith<T, S> for AsConvert<I>
+where
+    for<'a> I: From<&'a T>,
+    I: Serialize<S>,
+    S: Fallible + ?Sized,
+{
+    #[inline]
+    fn serialize_with(field: &T, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ConvertResolver::serialize(I::from(field), serializer)
+    }
+}
+
+impl<T, I, D> DeserializeWith<Archived<I>, T, D> for AsConvert<I>
+where
+    T: From<I>,
+    I: Archive,
+    Archived<I>: Deserialize<I, D>,
+    D: Fallible + ?Sized,
+{
+    #[inline]
+    fn deserialize_with(field: &Archived<I>, deserializer: &mut D) -> Result<T, D::Error> {
+        Ok(T::from(field.deserialize(deserializer)?))
+    }
+}
+
+impl<T, I: Archive> ArchiveWith<T> for AsTryConvert<I> {
+    type Archived = Archived<I>;
+    type Resolver = ConvertResolver<I>;
+
+    #[inline]
+    unsafe fn resolve_with(_: &T, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        resolver.resolve(pos, out);
+    }
+}
+
+impl<T, I, S> SerializeWith<T, S> for AsTryConvert<I>
+where
+    for<'a> I: TryFrom<&'a T>,
+    I: Serialize<S>,
+    S: Fallible + ?Sized,
+    for<'a> S::Error: From<<I as TryFrom<&'a T>>::Error>,
+{
+    #[inline]
+    fn serialize_with(field: &T, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ConvertResolver::serialize(I::try_from(field)?, serializer)
+    }
+}
+
+impl<T, I, D> DeserializeWith<Archived<I>, T, D> for AsTryConvert<I>
+where
+    T: TryFrom<I>,
+    I: Archive,
+    Archived<I>: Deserialize<I, D>,
+    D: Fallible + ?Sized,
+    D::Error: From<T::Error>,
+{
+    #[inline]
+    fn deserialize_with(field: &Archived<I>, deserializer: &mut D) -> Result<T, D::Error> {
+        Ok(T::try_from(field.deserialize(deserializer)?)?)
+    }
+}
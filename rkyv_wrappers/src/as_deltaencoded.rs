@@ -0,0 +1,552 @@
+//! A wrapper that delta encodes a sorted `Vec` of integers at serialization time.
+
+use crate::{
+    archived::{native, to_usize},
+    as_varintvec::VarInt,
+    varint,
+};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, ScratchVec,
+};
+use std::{error::Error, fmt, iter::FusedIterator, marker::PhantomData};
+
+/// A wrapper that delta encodes a sorted vector of integers into an [`ArchivedDeltaEncoded`].
+///
+/// Sorted sequences like timestamps and posting lists are mostly made up of small gaps between
+/// large values. `AsDeltaEncoded` splits the integers into blocks of `BLOCK_SIZE` integers, stores
+/// the first integer of each block whole, and stores every other integer as the LEB128-encoded
+/// difference from the integer before it. The first integer and byte offset of each block act as
+/// skip pointers, so searching for an integer only has to decode a single block. `BLOCK_SIZE` must
+/// be between 1 and `u32::MAX`, and other block sizes fail to compile.
+///
+/// `AsDeltaEncoded` works with `Vec<u32>` and `Vec<u64>`. The integers must be sorted in ascending
+/// order, and may repeat. Serializing an unsorted vector fails with an [`UnsortedError`], so the
+/// serializer's error type must implement `From<UnsortedError>`. See
+/// [Serializer errors](crate#serializer-errors).
+///
+/// With the `validation` feature, checking an `ArchivedDeltaEncoded` decodes every integer once to
+/// make sure that all of them are valid and sorted.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+/// use rkyv_wrappers::{as_deltaencoded::AsDeltaEncoded, BoxedErrorSerializer};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithTimestamps {
+///     #[with(AsDeltaEncoded<4>)]
+///     pub timestamps: Vec<u64>,
+/// }
+/// let original = StructWithTimestamps {
+///     timestamps: (0..10).map(|i| 1_600_000_000 + i * i).collect(),
+/// };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_inner();
+/// let output = unsafe {
+///     archived_root::<StructWithTimestamps>(&buffer)
+/// };
+/// assert_eq!(output.timestamps.get(3), Some(1_600_000_009));
+/// assert!(output.timestamps.contains(1_600_000_016));
+/// assert!(!output.timestamps.contains(1_600_000_017));
+/// assert_eq!(output.timestamps.seek_ge(1_600_000_017), Some((5, 1_600_000_025)));
+/// let deserialized: StructWithTimestamps = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+///
+/// A block size of zero does not compile:
+///
+/// ```compile_fail
+/// use rkyv::ser::Serializer;
+/// use rkyv_wrappers::{as_deltaencoded::AsDeltaEncoded, BoxedErrorSerializer};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize)]
+/// struct StructWithTimestamps {
+///     #[with(AsDeltaEncoded<0>)]
+///     pub timestamps: Vec<u64>,
+/// }
+/// let mut serializer = BoxedErrorSerializer::default();
+/// let result = serializer.serialize_value(&StructWithTimestamps { timestamps: Vec::new() });
+/// ```
+pub struct AsDeltaEncoded<const BLOCK_SIZE: usize = 64>;
+
+impl<const BLOCK_SIZE: usize> AsDeltaEncoded<BLOCK_SIZE> {
+    /// Fails to compile when `BLOCK_SIZE` is zero or larger than `u32::MAX`
+    const VALID_BLOCK_SIZE: () = assert!(
+        BLOCK_SIZE > 0 && BLOCK_SIZE <= u32::MAX as usize,
+        "AsDeltaEncoded requires a block size between 1 and u32::MAX"
+    );
+}
+
+/// An error returned by [`AsDeltaEncoded`] when a vector is not sorted.
+#[derive(Debug)]
+pub struct UnsortedError {
+    /// The index of the first integer that is less than the integer before it
+    pub index: usize,
+}
+
+impl fmt::Display for UnsortedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "unsorted: integer {} is less than the integer before it",
+            self.index,
+        )
+    }
+}
+
+impl Error for UnsortedError {}
+
+/// An archived sorted vector of delta-encoded integers.
+///
+/// This is the archived version of vectors labeled [`AsDeltaEncoded`]. Integers are decoded when
+/// they are accessed.
+///
+/// Accessing the integers of an archive that was not validated panics if the encoded data is
+/// malformed.
+#[repr(C)]
+pub struct ArchivedDeltaEncoded<T> {
+    len: Archived<u32>,
+    block_size: Archived<u32>,
+    firsts: ArchivedVec<Archived<u64>>,
+    offsets: ArchivedVec<Archived<u32>>,
+    data: ArchivedVec<u8>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: VarInt> ArchivedDeltaEncoded<T> {
+    /// Gets the number of integers in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        to_usize(self.len)
+    }
+
+    /// Returns whether the vector contains no integers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of integers in each block.
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        to_usize(self.block_size)
+    }
+
+    /// Gets the integer at the given position.
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut iter = self.iter();
+        iter.index = index - index % self.block_size();
+        iter.nth(index % self.block_size())
+    }
+
+    /// Returns whether the vector contains the given integer.
+    #[inline]
+    pub fn contains(&self, value: T) -> bool {
+        matches!(self.seek_ge(value), Some((_, found)) if found.to_varint() == value.to_varint())
+    }
+
+    /// Finds the first integer that is greater than or equal to `target`.
+    ///
+    /// Returns the position and value of the integer, or `None` if every integer is less than
+    /// `target`.
+    #[inline]
+    pub fn seek_ge(&self, target: T) -> Option<(usize, T)> {
+        let mut iter = self.iter();
+        let value = iter.seek_ge(target)?;
+        Some((iter.index - 1, value))
+    }
+
+    /// Gets an iterator over the integers in the vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            list: self,
+            index: 0,
+            pos: 0,
+            prev: 0,
+        }
+    }
+}
+
+impl<T: VarInt + fmt::Debug> fmt::Debug for ArchivedDeltaEncoded<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: VarInt> IntoIterator for &'a ArchivedDeltaEncoded<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the integers of an [`ArchivedDeltaEncoded`].
+pub struct Iter<'a, T> {
+    list: &'a ArchivedDeltaEncoded<T>,
+    index: usize,
+    pos: usize,
+    prev: u64,
+}
+
+impl<T: VarInt> Iter<'_, T> {
+    /// Advances the iterator to the first remaining integer that is greater than or equal to
+    /// `target` and returns it.
+    ///
+    /// Whole blocks of integers less than `target` are skipped without being decoded, which makes
+    /// this the building block for intersecting sorted lists.
+    pub fn seek_ge(&mut self, target: T) -> Option<T> {
+        let target = target.to_varint();
+        let block_size = self.list.block_size();
+        // Skip to the last remaining block that starts below the target, if it's not this one
+        let next_block = self.index / block_size + (self.index % block_size != 0) as usize;
+        if next_block < self.list.firsts.len() {
+            let skip = self.list.firsts[next_block..]
+                .partition_point(|&first| native::<u64>(first) < target);
+            if skip > 0 {
+                self.index = (next_block + skip - 1) * block_size;
+            }
+        }
+        self.find(|value| value.to_varint() >= target)
+    }
+}
+
+impl<T: VarInt> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.list.len() {
+            return None;
+        }
+        let block_size = self.list.block_size();
+        let value = if self.index % block_size == 0 {
+            let block = self.index / block_size;
+            self.pos = to_usize(self.list.offsets[block]);
+            native::<u64>(self.list.firsts[block])
+        } else {
+            varint::decode(&self.list.data, &mut self.pos)
+                .and_then(|delta| self.prev.checked_add(delta))
+                .expect("invalid delta-encoded integer")
+        };
+        self.prev = value;
+        self.index += 1;
+        Some(T::from_varint(value).expect("invalid delta-encoded integer"))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.list.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: VarInt> ExactSizeIterator for Iter<'_, T> {}
+impl<T: VarInt> FusedIterator for Iter<'_, T> {}
+
+/// Errors that can occur while checking an [`ArchivedDeltaEncoded`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum DeltaEncodedError<F, O, D> {
+    /// An error occurred while checking the first integers of the blocks
+    FirstsError(F),
+    /// An error occurred while checking the block offsets
+    OffsetsError(O),
+    /// An error occurred while checking the encoded deltas
+    DataError(D),
+    /// The block size is zero or the number of blocks does not match the number of integers
+    InvalidBlocks,
+    /// A block does not start where its offset says it does
+    InvalidBlockOffset {
+        /// The position of the block
+        block: usize,
+    },
+    /// An integer is truncated, overflows, or does not fit in the element type
+    InvalidValue {
+        /// The position of the integer
+        index: usize,
+    },
+    /// An integer is less than the integer before it
+    Unsorted {
+        /// The position of the integer
+        index: usize,
+    },
+    /// There are bytes left over after the last integer
+    TrailingBytes,
+}
+
+#[cfg(feature = "validation")]
+impl<F: fmt::Display, O: fmt::Display, D: fmt::Display> fmt::Display
+    for DeltaEncodedError<F, O, D>
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DeltaEncodedError::FirstsError(e) => write!(f, "firsts check error: {}", e),
+            DeltaEncodedError::OffsetsError(e) => write!(f, "offsets check error: {}", e),
+            DeltaEncodedError::DataError(e) => write!(f, "data check error: {}", e),
+            DeltaEncodedError::InvalidBlocks => {
+                write!(f, "invalid blocks: block size or count is wrong")
+            }
+            DeltaEncodedError::InvalidBlockOffset { block } => {
+                write!(f, "invalid block offset: for block {}", block)
+            }
+            DeltaEncodedError::InvalidValue { index } => {
+                write!(f, "invalid encoded integer: integer {}", index)
+            }
+            DeltaEncodedError::Unsorted { index } => write!(
+                f,
+                "unsorted: integer {} is less than the integer before it",
+                index
+            ),
+            DeltaEncodedError::TrailingBytes => write!(f, "trailing bytes after last integer"),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<F, O, D> Error for DeltaEncodedError<F, O, D>
+where
+    F: Error + 'static,
+    O: Error + 'static,
+    D: Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            DeltaEncodedError::FirstsError(e) => Some(e as &dyn Error),
+            DeltaEncodedError::OffsetsError(e) => Some(e as &dyn Error),
+            DeltaEncodedError::DataError(e) => Some(e as &dyn Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<T: VarInt, C> CheckBytes<C> for ArchivedDeltaEncoded<T>
+    where
+        C: ArchiveContext + ?Sized,
+        C::Error: Error,
+    {
+        type Error = DeltaEncodedError<
+            <ArchivedVec<Archived<u64>> as CheckBytes<C>>::Error,
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+            <ArchivedVec<u8> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let firsts = ArchivedVec::check_bytes(ptr::addr_of!((*value).firsts), context)
+                .map_err(DeltaEncodedError::FirstsError)?;
+            let offsets = ArchivedVec::check_bytes(ptr::addr_of!((*value).offsets), context)
+                .map_err(DeltaEncodedError::OffsetsError)?;
+            let data = ArchivedVec::check_bytes(ptr::addr_of!((*value).data), context)
+                .map_err(DeltaEncodedError::DataError)?;
+            let value = &*value;
+
+            let len = value.len();
+            let block_size = value.block_size();
+            if block_size == 0 {
+                return Err(DeltaEncodedError::InvalidBlocks);
+            }
+            // Both lengths come from the archive, so adding them could overflow
+            let blocks = len / block_size + (len % block_size != 0) as usize;
+            if firsts.len() != blocks || offsets.len() != blocks {
+                return Err(DeltaEncodedError::InvalidBlocks);
+            }
+
+            let data = data.as_slice();
+            let mut pos = 0;
+            let mut prev = 0u64;
+            for index in 0..len {
+                let current = if index % block_size == 0 {
+                    let block = index / block_size;
+                    if to_usize(offsets[block]) != pos {
+                        return Err(DeltaEncodedError::InvalidBlockOffset { block });
+                    }
+                    let first = native::<u64>(firsts[block]);
+                    if index > 0 && first < prev {
+                        return Err(DeltaEncodedError::Unsorted { index });
+                    }
+                    first
+                } else {
+                    varint::decode(data, &mut pos)
+                        .and_then(|delta| prev.checked_add(delta))
+                        .ok_or(DeltaEncodedError::InvalidValue { index })?
+                };
+                T::from_varint(current).ok_or(DeltaEncodedError::InvalidValue { index })?;
+                prev = current;
+            }
+            if pos != data.len() {
+                return Err(DeltaEncodedError::TrailingBytes);
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsDeltaEncoded`].
+pub struct DeltaEncodedResolver {
+    blocks: usize,
+    firsts: VecResolver,
+    offsets: VecResolver,
+    data_len: usize,
+    data: VecResolver,
+}
+
+macro_rules! impl_delta_encoded {
+    ($($ty:ty),*) => {
+        $(
+            impl<const BLOCK_SIZE: usize> ArchiveWith<Vec<$ty>> for AsDeltaEncoded<BLOCK_SIZE> {
+                type Archived = ArchivedDeltaEncoded<$ty>;
+                type Resolver = DeltaEncodedResolver;
+
+                #[inline]
+                unsafe fn resolve_with(
+                    field: &Vec<$ty>,
+                    pos: usize,
+                    resolver: Self::Resolver,
+                    out: *mut Self::Archived,
+                ) {
+                    let () = Self::VALID_BLOCK_SIZE;
+                    resolve_delta_encoded(field.len(), BLOCK_SIZE, pos, resolver, out);
+                }
+            }
+
+            /// # Panics
+            ///
+            /// Panics if the vector has more than `u32::MAX` integers or encodes to more than
+            /// `u32::MAX` bytes.
+            impl<S, const BLOCK_SIZE: usize> SerializeWith<Vec<$ty>, S>
+                for AsDeltaEncoded<BLOCK_SIZE>
+            where
+                S: ScratchSpace + Serializer + Fallible + ?Sized,
+                S::Error: From<UnsortedError>,
+            {
+                #[inline]
+                fn serialize_with(
+                    field: &Vec<$ty>,
+                    serializer: &mut S,
+                ) -> Result<Self::Resolver, S::Error> {
+                    let () = Self::VALID_BLOCK_SIZE;
+                    serialize_delta_encoded(field, BLOCK_SIZE, serializer)
+                }
+            }
+
+            impl<D: Fallible + ?Sized, const BLOCK_SIZE: usize>
+                DeserializeWith<ArchivedDeltaEncoded<$ty>, Vec<$ty>, D>
+                for AsDeltaEncoded<BLOCK_SIZE>
+            {
+                #[inline]
+                fn deserialize_with(
+                    field: &ArchivedDeltaEncoded<$ty>,
+                    _: &mut D,
+                ) -> Result<Vec<$ty>, D::Error> {
+                    Ok(field.iter().collect())
+                }
+            }
+        )*
+    };
+}
+
+impl_delta_encoded!(u32, u64);
+
+/// Resolves an [`ArchivedDeltaEncoded`] of `len` integers.
+#[inline]
+unsafe fn resolve_delta_encoded<T>(
+    len: usize,
+    block_size: usize,
+    pos: usize,
+    resolver: DeltaEncodedResolver,
+    out: *mut ArchivedDeltaEncoded<T>,
+) {
+    let (fp, fo) = out_field!(out.len);
+    (len as u32).resolve(pos + fp, (), fo);
+    let (fp, fo) = out_field!(out.block_size);
+    (block_size as u32).resolve(pos + fp, (), fo);
+    let (fp, fo) = out_field!(out.firsts);
+    ArchivedVec::resolve_from_len(resolver.blocks, pos + fp, resolver.firsts, fo);
+    let (fp, fo) = out_field!(out.offsets);
+    ArchivedVec::resolve_from_len(resolver.blocks, pos + fp, resolver.offsets, fo);
+    let (fp, fo) = out_field!(out.data);
+    ArchivedVec::resolve_from_len(resolver.data_len, pos + fp, resolver.data, fo);
+}
+
+/// Checks that `field` is sorted and serializes its blocks.
+fn serialize_delta_encoded<T: VarInt, S>(
+    field: &[T],
+    block_size: usize,
+    serializer: &mut S,
+) -> Result<DeltaEncodedResolver, S::Error>
+where
+    S: ScratchSpace + Serializer + ?Sized,
+    S::Error: From<UnsortedError>,
+{
+    assert!(
+        field.len() <= u32::MAX as usize,
+        "AsDeltaEncoded supports at most u32::MAX integers"
+    );
+    if let Some(index) = (1..field.len()).find(|&i| field[i].to_varint() < field[i - 1].to_varint())
+    {
+        return Err(UnsortedError { index }.into());
+    }
+
+    unsafe {
+        // Measure every block first so the deltas can be encoded in one go
+        let blocks = (field.len() + block_size - 1) / block_size;
+        let mut firsts = ScratchVec::new(serializer, blocks)?;
+        let mut offsets = ScratchVec::new(serializer, blocks)?;
+        let mut data_len = 0;
+        for (i, value) in field.iter().enumerate() {
+            if i % block_size == 0 {
+                firsts.push(value.to_varint());
+                offsets.push(data_len as u32);
+            } else {
+                data_len += varint::encoded_len(value.to_varint() - field[i - 1].to_varint());
+            }
+        }
+        assert!(
+            data_len <= u32::MAX as usize,
+            "AsDeltaEncoded supports at most u32::MAX bytes of encoded integers"
+        );
+
+        let mut data = ScratchVec::<u8>::new(serializer, data_len)?;
+        for (i, value) in field.iter().enumerate() {
+            if i % block_size != 0 {
+                let delta = value.to_varint() - field[i - 1].to_varint();
+                varint::encode(delta, |byte| data.push(byte));
+            }
+        }
+
+        let resolver = DeltaEncodedResolver {
+            blocks,
+            firsts: ArchivedVec::serialize_from_slice(&firsts, serializer)?,
+            offsets: ArchivedVec::serialize_from_slice(&offsets, serializer)?,
+            data_len: data.len(),
+            data: ArchivedVec::serialize_from_slice(&data, serializer)?,
+        };
+
+        data.free(serializer)?;
+        offsets.free(serializer)?;
+        firsts.free(serializer)?;
+
+        Ok(resolver)
+    }
+}
@@ -0,0 +1,356 @@
+//! Wrappers that archive floats as 16-bit floats at serialization time.
+
+use crate::archived::native;
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, Serialize,
+};
+use std::{cmp::Ordering, fmt};
+
+/// A wrapper that archives floats as IEEE 754 half-precision floats.
+///
+/// Half-precision floats have a 5-bit exponent and a 10-bit mantissa, so they take up half the
+/// space of an `f32` and a quarter of the space of an `f64`. They hold about three significant
+/// decimal digits, and their magnitude ranges from about `6.0e-8` to `65504`. Serializing rounds
+/// to the nearest half-precision float, finite values too large to represent become infinite, and
+/// NaNs stay NaN. Deserializing converts the archived value back exactly.
+///
+/// `AsF16` works with `f32`, `f64`, `Vec<f32>`, and `Vec<f64>`. Floats archive as [`ArchivedF16`]
+/// and vectors archive as `ArchivedVec<ArchivedF16>`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_f16::AsF16;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct StructWithWeights {
+///     #[with(AsF16)]
+///     pub bias: f64,
+///     #[with(AsF16)]
+///     pub weights: Vec<f32>,
+/// }
+/// let original = StructWithWeights {
+///     bias: 0.1,
+///     weights: vec![1.0, -0.5, 3.140625, 1000.0],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithWeights>(&buffer)
+/// };
+/// assert_eq!(output.bias.to_f64(), 0.0999755859375);
+/// assert_eq!(output.weights[2].to_f32(), 3.140625);
+/// let deserialized: StructWithWeights = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.weights, original.weights);
+/// ```
+pub struct AsF16;
+
+/// A wrapper that archives floats as bfloat16 floats.
+///
+/// bfloat16 floats have the same 8-bit exponent as an `f32` and a 7-bit mantissa, so they cover
+/// the same range as an `f32` in half the space but only hold about two significant decimal
+/// digits. Serializing rounds to the nearest bfloat16 float, finite values too large to represent
+/// become infinite, and NaNs stay NaN. Deserializing converts the archived value back exactly.
+///
+/// `AsBf16` works with `f32`, `f64`, `Vec<f32>`, and `Vec<f64>`. Floats archive as
+/// [`ArchivedBf16`] and vectors archive as `ArchivedVec<ArchivedBf16>`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_f16::AsBf16;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct StructWithGradients {
+///     #[with(AsBf16)]
+///     pub gradients: Vec<f32>,
+/// }
+/// let original = StructWithGradients {
+///     gradients: vec![1.0e30, -2.5, 1.0e-30],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithGradients>(&buffer)
+/// };
+/// assert_eq!(output.gradients[1].to_f32(), -2.5);
+/// assert!((output.gradients[0].to_f32() / 1.0e30 - 1.0).abs() < 0.01);
+/// let deserialized: StructWithGradients = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.gradients[1], -2.5);
+/// ```
+pub struct AsBf16;
+
+/// Rounds `value` to the nearest 16-bit float with the given number of mantissa bits, ties to
+/// even, and returns its bits.
+fn encode(value: f64, mantissa_bits: u32) -> u16 {
+    let exponent_max = (1u64 << (15 - mantissa_bits)) - 1;
+    let bias = (exponent_max >> 1) as i64;
+    let infinity = (exponent_max << mantissa_bits) as u16;
+
+    let bits = value.to_bits();
+    let sign = ((bits >> 63) as u16) << 15;
+    let exponent = ((bits >> 52) & 0x7ff) as i64;
+    let mantissa = bits & ((1 << 52) - 1);
+
+    if exponent == 0x7ff {
+        return if mantissa == 0 {
+            sign | infinity
+        } else {
+            // Keep the top of the payload and make sure the NaN stays quiet
+            let payload = (mantissa >> (52 - mantissa_bits)) as u16;
+            sign | infinity | (1 << (mantissa_bits - 1)) | payload
+        };
+    }
+    if exponent == 0 {
+        // Zero or an `f64` subnormal, which is far too small to represent
+        return sign;
+    }
+
+    let exponent = exponent - 1023 + bias;
+    let significand = mantissa | (1 << 52);
+    let shift = if exponent >= 1 {
+        i64::from(52 - mantissa_bits)
+    } else {
+        i64::from(52 - mantissa_bits) + 1 - exponent
+    };
+    if shift > 63 {
+        return sign;
+    }
+    let shift = shift as u32;
+
+    let mut rounded = significand >> shift;
+    let remainder = significand & ((1 << shift) - 1);
+    let half = 1 << (shift - 1);
+    if remainder > half || (remainder == half && rounded & 1 == 1) {
+        rounded += 1;
+    }
+
+    // Adding the significand carries into the exponent when rounding up overflows the mantissa
+    let result = if exponent >= 1 {
+        (((exponent - 1) as u64) << mantissa_bits) + rounded
+    } else {
+        rounded
+    };
+    if result >= u64::from(infinity) {
+        sign | infinity
+    } else {
+        sign | result as u16
+    }
+}
+
+/// Converts the bits of a 16-bit float with the given number of mantissa bits into an `f64`.
+fn decode(bits: u16, mantissa_bits: u32) -> f64 {
+    let exponent_max = (1u64 << (15 - mantissa_bits)) - 1;
+    let bias = (exponent_max >> 1) as i64;
+
+    let sign = u64::from(bits >> 15) << 63;
+    let exponent = (u64::from(bits) >> mantissa_bits) & exponent_max;
+    let mantissa = u64::from(bits) & ((1 << mantissa_bits) - 1);
+
+    if exponent == exponent_max {
+        f64::from_bits(sign | (0x7ff << 52) | (mantissa << (52 - mantissa_bits)))
+    } else if exponent == 0 {
+        let magnitude = mantissa as f64 * 2f64.powi((1 - bias) as i32 - mantissa_bits as i32);
+        f64::from_bits(sign | magnitude.to_bits())
+    } else {
+        let exponent = (exponent as i64 - bias + 1023) as u64;
+        f64::from_bits(sign | (exponent << 52) | (mantissa << (52 - mantissa_bits)))
+    }
+}
+
+macro_rules! impl_archived_float {
+    ($wrapper:ident, $archived:ident, $bits:ident, $mantissa_bits:expr, $name:literal) => {
+        #[doc = concat!("An archived ", $name, " float.")]
+        ///
+        #[doc = concat!(
+            "This is the archived version of floats labeled [`",
+            stringify!($wrapper),
+            "`]. Comparisons between archived floats follow the same rules as comparisons between ",
+            "the floats they convert to."
+        )]
+        #[derive(Clone, Copy)]
+        #[repr(transparent)]
+        pub struct $archived(Archived<u16>);
+
+        impl $archived {
+            /// Gets the bits of the float.
+            #[inline]
+            pub fn to_bits(self) -> u16 {
+                native::<u16>(self.0)
+            }
+
+            /// Converts the float to an `f32`. Every archived float can be represented exactly.
+            #[inline]
+            pub fn to_f32(self) -> f32 {
+                self.to_f64() as f32
+            }
+
+            /// Converts the float to an `f64`. Every archived float can be represented exactly.
+            #[inline]
+            pub fn to_f64(self) -> f64 {
+                decode(self.to_bits(), $mantissa_bits)
+            }
+        }
+
+        impl fmt::Debug for $archived {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Debug::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl fmt::Display for $archived {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                fmt::Display::fmt(&self.to_f32(), f)
+            }
+        }
+
+        impl PartialEq for $archived {
+            #[inline]
+            fn eq(&self, other: &Self) -> bool {
+                self.to_f32() == other.to_f32()
+            }
+        }
+
+        impl PartialOrd for $archived {
+            #[inline]
+            fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+                self.to_f32().partial_cmp(&other.to_f32())
+            }
+        }
+
+        impl From<$archived> for f32 {
+            #[inline]
+            fn from(value: $archived) -> Self {
+                value.to_f32()
+            }
+        }
+
+        impl From<$archived> for f64 {
+            #[inline]
+            fn from(value: $archived) -> Self {
+                value.to_f64()
+            }
+        }
+
+        // Every bit pattern is a valid float
+        #[cfg(feature = "validation")]
+        impl<C: ?Sized> rkyv::bytecheck::CheckBytes<C> for $archived {
+            type Error = std::convert::Infallible;
+
+            #[inline]
+            unsafe fn check_bytes<'a>(
+                value: *const Self,
+                _: &mut C,
+            ) -> Result<&'a Self, Self::Error> {
+                Ok(&*value)
+            }
+        }
+
+        /// The rounded bits of a float, which archive as the archived float.
+        struct $bits(u16);
+
+        impl $bits {
+            #[inline]
+            fn new(value: f64) -> Self {
+                Self(encode(value, $mantissa_bits))
+            }
+        }
+
+        impl Archive for $bits {
+            type Archived = $archived;
+            type Resolver = ();
+
+            #[inline]
+            unsafe fn resolve(&self, pos: usize, _: (), out: *mut Self::Archived) {
+                let (fp, fo) = out_field!(out.0);
+                self.0.resolve(pos + fp, (), fo);
+            }
+        }
+
+        impl<S: Fallible + ?Sized> Serialize<S> for $bits {
+            #[inline]
+            fn serialize(&self, _: &mut S) -> Result<(), S::Error> {
+                Ok(())
+            }
+        }
+
+        impl_as_float!($wrapper, $archived, $bits, f32);
+        impl_as_float!($wrapper, $archived, $bits, f64);
+    };
+}
+
+macro_rules! impl_as_float {
+    ($wrapper:ident, $archived:ident, $bits:ident, $float:ty) => {
+        impl ArchiveWith<$float> for $wrapper {
+            type Archived = $archived;
+            type Resolver = ();
+
+            #[inline]
+            unsafe fn resolve_with(field: &$float, pos: usize, _: (), out: *mut Self::Archived) {
+                $bits::new(f64::from(*field)).resolve(pos, (), out);
+            }
+        }
+
+        impl<S: Fallible + ?Sized> SerializeWith<$float, S> for $wrapper {
+            #[inline]
+            fn serialize_with(_: &$float, _: &mut S) -> Result<(), S::Error> {
+                Ok(())
+            }
+        }
+
+        impl<D: Fallible + ?Sized> DeserializeWith<$archived, $float, D> for $wrapper {
+            #[inline]
+            fn deserialize_with(field: &$archived, _: &mut D) -> Result<$float, D::Error> {
+                Ok(field.to_f64() as $float)
+            }
+        }
+
+        impl ArchiveWith<Vec<$float>> for $wrapper {
+            type Archived = ArchivedVec<$archived>;
+            type Resolver = VecResolver;
+
+            #[inline]
+            unsafe fn resolve_with(
+                field: &Vec<$float>,
+                pos: usize,
+                resolver: Self::Resolver,
+                out: *mut Self::Archived,
+            ) {
+                ArchivedVec::resolve_from_len(field.len(), pos, resolver, out);
+            }
+        }
+
+        impl<S> SerializeWith<Vec<$float>, S> for $wrapper
+        where
+            S: ScratchSpace + Serializer + Fallible + ?Sized,
+        {
+            #[inline]
+            fn serialize_with(
+                field: &Vec<$float>,
+                serializer: &mut S,
+            ) -> Result<Self::Resolver, S::Error> {
+                ArchivedVec::serialize_from_iter::<$bits, _, _, _>(
+                    field.iter().map(|&value| $bits::new(f64::from(value))),
+                    serializer,
+                )
+            }
+        }
+
+        impl<D: Fallible + ?Sized> DeserializeWith<ArchivedVec<$archived>, Vec<$float>, D>
+            for $wrapper
+        {
+            #[inline]
+            fn deserialize_with(
+                field: &ArchivedVec<$archived>,
+                _: &mut D,
+            ) -> Result<Vec<$float>, D::Error> {
+                Ok(field.iter().map(|value| value.to_f64() as $float).collect())
+            }
+        }
+    };
+}
+
+impl_archived_float!(AsF16, ArchivedF16, F16Bits, 10, "IEEE 754 half-precision");
+impl_archived_float!(AsBf16, ArchivedBf16, Bf16Bits, 7, "bfloat16");
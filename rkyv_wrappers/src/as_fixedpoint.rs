@@ -0,0 +1,461 @@
+//! A wrapper that archives floats and decimals as fixed-point numbers.
+
+use crate::archived::native;
+use rkyv::{
+    out_field,
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible,
+};
+use std::{cmp::Ordering, error::Error, fmt};
+
+/// A wrapper that archives a float as an [`ArchivedFixedPoint`] with `SCALE` decimal places.
+///
+/// Prices and other decimal quantities archived as floats get rounded differently by every reader.
+/// `AsFixedPoint` rounds the value once during serialization to the nearest multiple of
+/// `10^-SCALE`, with ties rounding away from zero, and archives it as an `i64` count of those
+/// multiples. Arithmetic on the archived values is exact integer arithmetic, and formatting them
+/// prints exactly `SCALE` decimal places.
+///
+/// Floats are rounded from their shortest decimal representation, so `0.145` rounds to `0.15` with
+/// two decimal places even though the float closest to `0.145` is slightly less than it.
+///
+/// `AsFixedPoint` works with `f64` and, with the `rust_decimal` feature, `rust_decimal::Decimal`.
+/// `SCALE` must be at most 18 so that `10^SCALE` fits in an `i64`, and larger scales fail to
+/// compile. Serializing NaN or a value that
+/// does not fit fails with a [`FixedPointError`], so the serializer's error type must implement
+/// `From<FixedPointError>` (see [Serializer errors](crate#serializer-errors)). Deserializing into
+/// an `f64` rounds to the nearest float, and deserializing into a `Decimal` is exact.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+/// use rkyv_wrappers::{
+///     as_fixedpoint::{AsFixedPoint, FixedPointError},
+///     BoxedErrorSerializer,
+/// };
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct LineItem {
+///     #[with(AsFixedPoint<2>)]
+///     pub price: f64,
+///     #[with(AsFixedPoint<2>)]
+///     pub discount: f64,
+/// }
+/// let original = LineItem {
+///     price: 19.99,
+///     discount: 0.1 + 0.2,
+/// };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_inner();
+/// let output = unsafe {
+///     archived_root::<LineItem>(&buffer)
+/// };
+/// assert_eq!(output.price.raw(), 1999);
+/// assert_eq!(output.discount.to_string(), "0.30");
+/// let total = output.price.checked_sub(&output.discount).unwrap();
+/// assert_eq!(total.to_string(), "19.69");
+/// assert_eq!(output.price.checked_mul_int(3).unwrap().to_string(), "59.97");
+/// let deserialized: LineItem = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.discount, 0.3);
+///
+/// let broken = LineItem {
+///     price: f64::NAN,
+///     discount: 0.0,
+/// };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// let error = serializer.serialize_value(&broken).unwrap_err();
+/// assert!(matches!(
+///     error.downcast_ref::<FixedPointError>(),
+///     Some(FixedPointError::NotANumber)
+/// ));
+/// ```
+///
+/// A scale of 19 does not compile:
+///
+/// ```compile_fail
+/// use rkyv_wrappers::as_fixedpoint::FixedPoint;
+///
+/// let value = FixedPoint::<19>::from_raw(1);
+/// ```
+pub struct AsFixedPoint<const SCALE: u32>;
+
+/// An error returned when a value cannot be converted to a fixed-point number.
+#[derive(Debug)]
+pub enum FixedPointError {
+    /// The value is NaN
+    NotANumber,
+    /// The value is too large to represent
+    Overflow,
+}
+
+impl fmt::Display for FixedPointError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FixedPointError::NotANumber => {
+                write!(f, "not a number: NaN has no fixed-point representation")
+            }
+            FixedPointError::Overflow => {
+                write!(f, "overflow: value is too large for a fixed-point number")
+            }
+        }
+    }
+}
+
+impl Error for FixedPointError {}
+
+/// Formats a fixed-point number with exactly `SCALE` decimal places.
+fn format_raw<const SCALE: u32>(raw: i64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    let sign = if raw < 0 { "-" } else { "" };
+    let magnitude = raw.unsigned_abs();
+    if SCALE == 0 {
+        write!(f, "{}{}", sign, magnitude)
+    } else {
+        let factor = FixedPoint::<SCALE>::FACTOR.unsigned_abs();
+        write!(
+            f,
+            "{}{}.{:0width$}",
+            sign,
+            magnitude / factor,
+            magnitude % factor,
+            width = SCALE as usize,
+        )
+    }
+}
+
+/// A fixed-point number with `SCALE` decimal places.
+///
+/// This is the value of an [`ArchivedFixedPoint`] and the result of arithmetic on one. It holds
+/// the number multiplied by `10^SCALE` as an `i64`.
+#[derive(Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct FixedPoint<const SCALE: u32> {
+    raw: i64,
+}
+
+impl<const SCALE: u32> FixedPoint<SCALE> {
+    /// Fails to compile when `SCALE` is too large for `10^SCALE` to fit in an `i64`
+    ///
+    /// Every constructor and every impl for `SCALE` references this, so that an unsupported scale
+    /// is a compile error instead of an overflow at runtime.
+    const VALID_SCALE: () = assert!(
+        SCALE <= 18,
+        "the scale of a fixed-point number must be at most 18"
+    );
+
+    /// The number of multiples of `10^-SCALE` in one
+    const FACTOR: i64 = {
+        let () = Self::VALID_SCALE;
+        10i64.pow(SCALE)
+    };
+
+    /// Creates a fixed-point number from the number multiplied by `10^SCALE`.
+    #[inline]
+    pub const fn from_raw(raw: i64) -> Self {
+        let () = Self::VALID_SCALE;
+        Self { raw }
+    }
+
+    /// Gets the number multiplied by `10^SCALE`.
+    #[inline]
+    pub const fn raw(self) -> i64 {
+        self.raw
+    }
+
+    /// Rounds a float to the nearest fixed-point number, with ties rounding away from zero.
+    ///
+    /// The float is rounded from its shortest decimal representation, which is the one that
+    /// `Display` prints.
+    pub fn from_f64(value: f64) -> Result<Self, FixedPointError> {
+        use std::convert::TryFrom;
+
+        if value.is_nan() {
+            return Err(FixedPointError::NotANumber);
+        }
+        if value.is_infinite() {
+            return Err(FixedPointError::Overflow);
+        }
+
+        let formatted = format!("{:e}", value.abs());
+        let (mantissa, exponent) = formatted.split_once('e').unwrap();
+        let digits = mantissa.replace('.', "");
+        let significand = digits.parse::<u128>().unwrap();
+        let shift = exponent.parse::<i32>().unwrap() - (digits.len() as i32 - 1) + SCALE as i32;
+
+        let magnitude = if shift >= 0 {
+            10u128
+                .checked_pow(shift as u32)
+                .and_then(|factor| factor.checked_mul(significand))
+        } else {
+            Some(match 10u128.checked_pow(shift.unsigned_abs()) {
+                Some(divisor) => {
+                    let remainder = significand % divisor;
+                    significand / divisor + u128::from(remainder >= divisor - remainder)
+                }
+                None => 0,
+            })
+        };
+        magnitude
+            .and_then(|magnitude| i128::try_from(magnitude).ok())
+            .map(|magnitude| if value < 0.0 { -magnitude } else { magnitude })
+            .and_then(|raw| i64::try_from(raw).ok())
+            .map(Self::from_raw)
+            .ok_or(FixedPointError::Overflow)
+    }
+
+    /// Converts the number to the nearest float.
+    #[inline]
+    pub fn to_f64(self) -> f64 {
+        self.raw as f64 / Self::FACTOR as f64
+    }
+
+    /// Rounds a decimal to the nearest fixed-point number, with ties rounding away from zero.
+    #[cfg(feature = "rust_decimal")]
+    pub fn from_decimal(value: rust_decimal::Decimal) -> Result<Self, FixedPointError> {
+        use rust_decimal::RoundingStrategy;
+        use std::convert::TryFrom;
+
+        let rounded = value.round_dp_with_strategy(SCALE, RoundingStrategy::MidpointAwayFromZero);
+        10i128
+            .pow(SCALE - rounded.scale())
+            .checked_mul(rounded.mantissa())
+            .and_then(|raw| i64::try_from(raw).ok())
+            .map(Self::from_raw)
+            .ok_or(FixedPointError::Overflow)
+    }
+
+    /// Converts the number to a decimal exactly.
+    #[cfg(feature = "rust_decimal")]
+    #[inline]
+    pub fn to_decimal(self) -> rust_decimal::Decimal {
+        let () = Self::VALID_SCALE;
+        rust_decimal::Decimal::new(self.raw, SCALE)
+    }
+
+    /// Adds two numbers, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_add(self, rhs: Self) -> Option<Self> {
+        self.raw.checked_add(rhs.raw).map(Self::from_raw)
+    }
+
+    /// Subtracts two numbers, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_sub(self, rhs: Self) -> Option<Self> {
+        self.raw.checked_sub(rhs.raw).map(Self::from_raw)
+    }
+
+    /// Multiplies the number by an integer, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_mul_int(self, rhs: i64) -> Option<Self> {
+        self.raw.checked_mul(rhs).map(Self::from_raw)
+    }
+}
+
+impl<const SCALE: u32> fmt::Debug for FixedPoint<SCALE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_raw::<SCALE>(self.raw, f)
+    }
+}
+
+impl<const SCALE: u32> fmt::Display for FixedPoint<SCALE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_raw::<SCALE>(self.raw, f)
+    }
+}
+
+/// An archived fixed-point number with `SCALE` decimal places.
+///
+/// This is the archived version of values labeled [`AsFixedPoint`].
+#[repr(transparent)]
+pub struct ArchivedFixedPoint<const SCALE: u32>(Archived<i64>);
+
+impl<const SCALE: u32> ArchivedFixedPoint<SCALE> {
+    /// Gets the number multiplied by `10^SCALE`.
+    #[inline]
+    pub fn raw(&self) -> i64 {
+        native::<i64>(self.0)
+    }
+
+    /// Gets the value of the number.
+    #[inline]
+    pub fn get(&self) -> FixedPoint<SCALE> {
+        FixedPoint::from_raw(self.raw())
+    }
+
+    /// Converts the number to the nearest float.
+    #[inline]
+    pub fn to_f64(&self) -> f64 {
+        self.get().to_f64()
+    }
+
+    /// Converts the number to a decimal exactly.
+    #[cfg(feature = "rust_decimal")]
+    #[inline]
+    pub fn to_decimal(&self) -> rust_decimal::Decimal {
+        self.get().to_decimal()
+    }
+
+    /// Adds two numbers, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_add(&self, rhs: &Self) -> Option<FixedPoint<SCALE>> {
+        self.get().checked_add(rhs.get())
+    }
+
+    /// Subtracts two numbers, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_sub(&self, rhs: &Self) -> Option<FixedPoint<SCALE>> {
+        self.get().checked_sub(rhs.get())
+    }
+
+    /// Multiplies the number by an integer, returning `None` if the result overflows.
+    #[inline]
+    pub fn checked_mul_int(&self, rhs: i64) -> Option<FixedPoint<SCALE>> {
+        self.get().checked_mul_int(rhs)
+    }
+}
+
+impl<const SCALE: u32> fmt::Debug for ArchivedFixedPoint<SCALE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_raw::<SCALE>(self.raw(), f)
+    }
+}
+
+impl<const SCALE: u32> fmt::Display for ArchivedFixedPoint<SCALE> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        format_raw::<SCALE>(self.raw(), f)
+    }
+}
+
+impl<const SCALE: u32> PartialEq for ArchivedFixedPoint<SCALE> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.raw() == other.raw()
+    }
+}
+
+impl<const SCALE: u32> Eq for ArchivedFixedPoint<SCALE> {}
+
+impl<const SCALE: u32> PartialOrd for ArchivedFixedPoint<SCALE> {
+    #[inline]
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<const SCALE: u32> Ord for ArchivedFixedPoint<SCALE> {
+    #[inline]
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.raw().cmp(&other.raw())
+    }
+}
+
+impl<const SCALE: u32> PartialEq<FixedPoint<SCALE>> for ArchivedFixedPoint<SCALE> {
+    #[inline]
+    fn eq(&self, other: &FixedPoint<SCALE>) -> bool {
+        self.raw() == other.raw()
+    }
+}
+
+// Every bit pattern is a valid fixed-point number
+#[cfg(feature = "validation")]
+impl<C: ?Sized, const SCALE: u32> rkyv::bytecheck::CheckBytes<C> for ArchivedFixedPoint<SCALE> {
+    type Error = std::convert::Infallible;
+
+    #[inline]
+    unsafe fn check_bytes<'a>(value: *const Self, _: &mut C) -> Result<&'a Self, Self::Error> {
+        Ok(&*value)
+    }
+}
+
+/// The resolver for [`AsFixedPoint`].
+pub struct FixedPointResolver {
+    raw: i64,
+}
+
+impl<const SCALE: u32> ArchiveWith<f64> for AsFixedPoint<SCALE> {
+    type Archived = ArchivedFixedPoint<SCALE>;
+    type Resolver = FixedPointResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        _: &f64,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let () = FixedPoint::<SCALE>::VALID_SCALE;
+        let (fp, fo) = out_field!(out.0);
+        resolver.raw.resolve(pos + fp, (), fo);
+    }
+}
+
+impl<S, const SCALE: u32> SerializeWith<f64, S> for AsFixedPoint<SCALE>
+where
+    S: Fallible + ?Sized,
+    S::Error: From<FixedPointError>,
+{
+    #[inline]
+    fn serialize_with(field: &f64, _: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(FixedPointResolver {
+            raw: FixedPoint::<SCALE>::from_f64(*field)?.raw(),
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized, const SCALE: u32> DeserializeWith<ArchivedFixedPoint<SCALE>, f64, D>
+    for AsFixedPoint<SCALE>
+{
+    #[inline]
+    fn deserialize_with(field: &ArchivedFixedPoint<SCALE>, _: &mut D) -> Result<f64, D::Error> {
+        Ok(field.to_f64())
+    }
+}
+
+#[cfg(feature = "rust_decimal")]
+const _: () = {
+    use rust_decimal::Decimal;
+
+    impl<const SCALE: u32> ArchiveWith<Decimal> for AsFixedPoint<SCALE> {
+        type Archived = ArchivedFixedPoint<SCALE>;
+        type Resolver = FixedPointResolver;
+
+        #[inline]
+        unsafe fn resolve_with(
+            _: &Decimal,
+            pos: usize,
+            resolver: Self::Resolver,
+            out: *mut Self::Archived,
+        ) {
+            let () = FixedPoint::<SCALE>::VALID_SCALE;
+            let (fp, fo) = out_field!(out.0);
+            resolver.raw.resolve(pos + fp, (), fo);
+        }
+    }
+
+    impl<S, const SCALE: u32> SerializeWith<Decimal, S> for AsFixedPoint<SCALE>
+    where
+        S: Fallible + ?Sized,
+        S::Error: From<FixedPointError>,
+    {
+        #[inline]
+        fn serialize_with(field: &Decimal, _: &mut S) -> Result<Self::Resolver, S::Error> {
+            Ok(FixedPointResolver {
+                raw: FixedPoint::<SCALE>::from_decimal(*field)?.raw(),
+            })
+        }
+    }
+
+    impl<D, const SCALE: u32> DeserializeWith<ArchivedFixedPoint<SCALE>, Decimal, D>
+        for AsFixedPoint<SCALE>
+    where
+        D: Fallible + ?Sized,
+    {
+        #[inline]
+        fn deserialize_with(
+            field: &ArchivedFixedPoint<SCALE>,
+            _: &mut D,
+        ) -> Result<Decimal, D::Error> {
+            Ok(field.to_decimal())
+        }
+    }
+};
@@ -0,0 +1,513 @@
+//! A wrapper that front codes a `Vec` of strings at serialization time.
+
+use crate::{archived::to_usize, varint};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, ScratchVec,
+};
+use std::{fmt, iter::FusedIterator, str};
+
+/// A wrapper that front codes a vector of strings into an [`ArchivedFrontCodedStrings`].
+///
+/// Sorted lists of URLs, paths, and other keys tend to share long prefixes with their neighbors.
+/// `AsFrontCodedStrings` splits the strings into blocks of `BLOCK_SIZE` strings. The first string
+/// of each block is stored whole, and every other string only stores the length of the prefix it
+/// shares with the string before it followed by the rest of its bytes. An index of where each
+/// block starts lets the archived list find a string by decoding at most one block.
+///
+/// Larger blocks save more space, while smaller blocks make random access faster. `BLOCK_SIZE`
+/// must be between 1 and `u32::MAX`, and other block sizes fail to compile. The strings do
+/// not need to be sorted, but [`binary_search`](ArchivedFrontCodedStrings::binary_search) only
+/// gives meaningful results when they are.
+///
+/// With the `validation` feature, checking an `ArchivedFrontCodedStrings` decodes every block
+/// once to make sure that all of the strings are valid UTF-8 and fit inside their block.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_frontcodedstrings::AsFrontCodedStrings;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithPaths {
+///     #[with(AsFrontCodedStrings<4>)]
+///     pub paths: Vec<String>,
+/// }
+/// let original = StructWithPaths {
+///     paths: (0..10).map(|i| format!("/usr/share/doc/{}", i)).collect(),
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithPaths>(&buffer)
+/// };
+/// assert_eq!(output.paths.get(6).unwrap(), "/usr/share/doc/6");
+/// assert_eq!(output.paths.binary_search("/usr/share/doc/3"), Ok(3));
+/// assert_eq!(output.paths.iter().count(), 10);
+/// let deserialized: StructWithPaths = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+///
+/// A block size of zero does not compile:
+///
+/// ```compile_fail
+/// use rkyv_wrappers::as_frontcodedstrings::AsFrontCodedStrings;
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize)]
+/// struct StructWithPaths {
+///     #[with(AsFrontCodedStrings<0>)]
+///     pub paths: Vec<String>,
+/// }
+/// let buffer = rkyv::to_bytes::<_, 256>(&StructWithPaths { paths: Vec::new() });
+/// ```
+pub struct AsFrontCodedStrings<const BLOCK_SIZE: usize = 16>;
+
+impl<const BLOCK_SIZE: usize> AsFrontCodedStrings<BLOCK_SIZE> {
+    /// Fails to compile when `BLOCK_SIZE` is zero or larger than `u32::MAX`
+    const VALID_BLOCK_SIZE: () = assert!(
+        BLOCK_SIZE > 0 && BLOCK_SIZE <= u32::MAX as usize,
+        "AsFrontCodedStrings requires a block size between 1 and u32::MAX"
+    );
+}
+
+/// An archived list of front-coded strings.
+///
+/// This is the archived version of vectors labeled [`AsFrontCodedStrings`]. Strings are decoded
+/// into new `String`s when they are accessed.
+///
+/// Accessing the strings of an archive that was not validated panics if the encoded data is
+/// malformed.
+#[repr(C)]
+pub struct ArchivedFrontCodedStrings {
+    len: Archived<u32>,
+    block_size: Archived<u32>,
+    blocks: ArchivedVec<Archived<u32>>,
+    data: ArchivedVec<u8>,
+}
+
+impl ArchivedFrontCodedStrings {
+    /// Gets the number of strings in the list.
+    #[inline]
+    pub fn len(&self) -> usize {
+        to_usize(self.len)
+    }
+
+    /// Returns whether the list contains no strings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of strings in each block.
+    #[inline]
+    pub fn block_size(&self) -> usize {
+        to_usize(self.block_size)
+    }
+
+    #[inline]
+    fn iter_from_block(&self, block: usize) -> Iter<'_> {
+        Iter {
+            data: self.data.as_slice(),
+            pos: to_usize(self.blocks[block]),
+            block_size: self.block_size(),
+            index: block * self.block_size(),
+            len: self.len(),
+            current: String::new(),
+        }
+    }
+
+    /// Gets the string at the given position.
+    pub fn get(&self, index: usize) -> Option<String> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut iter = self.iter_from_block(index / self.block_size());
+        for _ in 0..=index % self.block_size() {
+            iter.advance();
+        }
+        Some(iter.current)
+    }
+
+    /// Searches the list for the given string.
+    ///
+    /// Like `slice::binary_search`, this returns the position of the string if it is found and the
+    /// position where it could be inserted to keep the list sorted otherwise. If the list is not
+    /// sorted, the result is meaningless.
+    pub fn binary_search(&self, s: &str) -> Result<usize, usize> {
+        let data = self.data.as_slice();
+        // The first string of each block is stored whole and can be compared in place
+        let block = self.blocks.as_slice().partition_point(|&offset| {
+            let mut pos = to_usize(offset);
+            let len = read_len(data, &mut pos);
+            &data[pos..pos + len] <= s.as_bytes()
+        });
+        if block == 0 {
+            return Err(0);
+        }
+
+        let block = block - 1;
+        let start = block * self.block_size();
+        let end = (start + self.block_size()).min(self.len());
+        let mut iter = self.iter_from_block(block);
+        for index in start..end {
+            iter.advance();
+            match iter.current.as_str().cmp(s) {
+                std::cmp::Ordering::Less => {}
+                std::cmp::Ordering::Equal => return Ok(index),
+                std::cmp::Ordering::Greater => return Err(index),
+            }
+        }
+        Err(end)
+    }
+
+    /// Gets an iterator over the strings in the list.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_> {
+        if self.is_empty() {
+            Iter {
+                data: &[],
+                pos: 0,
+                block_size: 1,
+                index: 0,
+                len: 0,
+                current: String::new(),
+            }
+        } else {
+            self.iter_from_block(0)
+        }
+    }
+}
+
+impl fmt::Debug for ArchivedFrontCodedStrings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a ArchivedFrontCodedStrings {
+    type Item = String;
+    type IntoIter = Iter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[inline]
+fn read_len(data: &[u8], pos: &mut usize) -> usize {
+    varint::decode(data, pos).expect("invalid front-coded string") as usize
+}
+
+/// An iterator over the strings of an [`ArchivedFrontCodedStrings`].
+pub struct Iter<'a> {
+    data: &'a [u8],
+    pos: usize,
+    block_size: usize,
+    index: usize,
+    len: usize,
+    current: String,
+}
+
+impl Iter<'_> {
+    /// Decodes the next string into `current`.
+    fn advance(&mut self) -> bool {
+        if self.index >= self.len {
+            return false;
+        }
+        if self.index % self.block_size == 0 {
+            self.current.clear();
+        } else {
+            let shared = read_len(self.data, &mut self.pos);
+            self.current.truncate(shared);
+        }
+        let suffix_len = read_len(self.data, &mut self.pos);
+        let suffix = &self.data[self.pos..self.pos + suffix_len];
+        self.pos += suffix_len;
+        self.current
+            .push_str(str::from_utf8(suffix).expect("invalid front-coded string"));
+        self.index += 1;
+        true
+    }
+}
+
+impl Iterator for Iter<'_> {
+    type Item = String;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.advance() {
+            Some(self.current.clone())
+        } else {
+            None
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+/// Errors that can occur while checking an [`ArchivedFrontCodedStrings`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum FrontCodedStringsError<B, D> {
+    /// An error occurred while checking the block index
+    BlocksError(B),
+    /// An error occurred while checking the encoded strings
+    DataError(D),
+    /// The block size is zero or the number of blocks does not match the number of strings
+    InvalidBlocks,
+    /// A block does not start where the block index says it does
+    InvalidBlockOffset {
+        /// The position of the block
+        block: usize,
+    },
+    /// A string is truncated, shares more than the string before it, or is not valid UTF-8
+    InvalidString {
+        /// The position of the string
+        index: usize,
+    },
+    /// There are bytes left over after the last string
+    TrailingBytes,
+}
+
+#[cfg(feature = "validation")]
+impl<B: fmt::Display, D: fmt::Display> fmt::Display for FrontCodedStringsError<B, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FrontCodedStringsError::BlocksError(e) => write!(f, "blocks check error: {}", e),
+            FrontCodedStringsError::DataError(e) => write!(f, "data check error: {}", e),
+            FrontCodedStringsError::InvalidBlocks => {
+                write!(f, "invalid blocks: block size or count is wrong")
+            }
+            FrontCodedStringsError::InvalidBlockOffset { block } => {
+                write!(f, "invalid block offset: for block {}", block)
+            }
+            FrontCodedStringsError::InvalidString { index } => {
+                write!(f, "invalid encoded string: string {}", index)
+            }
+            FrontCodedStringsError::TrailingBytes => write!(f, "trailing bytes after last string"),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<B, D> std::error::Error for FrontCodedStringsError<B, D>
+where
+    B: std::error::Error + 'static,
+    D: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            FrontCodedStringsError::BlocksError(e) => Some(e as &dyn std::error::Error),
+            FrontCodedStringsError::DataError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<C> CheckBytes<C> for ArchivedFrontCodedStrings
+    where
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = FrontCodedStringsError<
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+            <ArchivedVec<u8> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let blocks = ArchivedVec::check_bytes(ptr::addr_of!((*value).blocks), context)
+                .map_err(FrontCodedStringsError::BlocksError)?;
+            let data = ArchivedVec::check_bytes(ptr::addr_of!((*value).data), context)
+                .map_err(FrontCodedStringsError::DataError)?;
+            let value = &*value;
+
+            let len = value.len();
+            let block_size = value.block_size();
+            // Both lengths come from the archive, so adding them could overflow
+            if block_size == 0
+                || blocks.len() != len / block_size + (len % block_size != 0) as usize
+            {
+                return Err(FrontCodedStringsError::InvalidBlocks);
+            }
+
+            // Decode every string, keeping the last one around to check shared prefixes against
+            let data = data.as_slice();
+            let mut pos = 0;
+            let mut current = Vec::new();
+            for index in 0..len {
+                let invalid = || FrontCodedStringsError::InvalidString { index };
+                if index % block_size == 0 {
+                    let block = index / block_size;
+                    if to_usize(blocks[block]) != pos {
+                        return Err(FrontCodedStringsError::InvalidBlockOffset { block });
+                    }
+                    current.clear();
+                } else {
+                    let shared = varint::decode(data, &mut pos).ok_or_else(invalid)? as usize;
+                    // `current` is valid UTF-8, so the prefix ends on a character boundary unless
+                    // the next byte continues a character
+                    if shared > current.len()
+                        || (shared < current.len() && current[shared] & 0xc0 == 0x80)
+                    {
+                        return Err(invalid());
+                    }
+                    current.truncate(shared);
+                }
+                let suffix_len = varint::decode(data, &mut pos).ok_or_else(invalid)? as usize;
+                let suffix = data
+                    .get(pos..pos.saturating_add(suffix_len))
+                    .ok_or_else(invalid)?;
+                str::from_utf8(suffix).map_err(|_| invalid())?;
+                current.extend_from_slice(suffix);
+                pos += suffix_len;
+            }
+            if pos != data.len() {
+                return Err(FrontCodedStringsError::TrailingBytes);
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsFrontCodedStrings`].
+pub struct FrontCodedStringsResolver {
+    blocks_len: usize,
+    blocks: VecResolver,
+    data_len: usize,
+    data: VecResolver,
+}
+
+impl<const BLOCK_SIZE: usize> ArchiveWith<Vec<String>> for AsFrontCodedStrings<BLOCK_SIZE> {
+    type Archived = ArchivedFrontCodedStrings;
+    type Resolver = FrontCodedStringsResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<String>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let () = Self::VALID_BLOCK_SIZE;
+        let (fp, fo) = out_field!(out.len);
+        (field.len() as u32).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.block_size);
+        (BLOCK_SIZE as u32).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.blocks);
+        ArchivedVec::resolve_from_len(resolver.blocks_len, pos + fp, resolver.blocks, fo);
+        let (fp, fo) = out_field!(out.data);
+        ArchivedVec::resolve_from_len(resolver.data_len, pos + fp, resolver.data, fo);
+    }
+}
+
+/// Returns the length of the prefix `s` shares with `prev` that ends on a character boundary.
+#[inline]
+fn shared_prefix(prev: &str, s: &str) -> usize {
+    let mut shared = prev
+        .bytes()
+        .zip(s.bytes())
+        .take_while(|(a, b)| a == b)
+        .count();
+    while !s.is_char_boundary(shared) {
+        shared -= 1;
+    }
+    shared
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` strings or encodes to more than `u32::MAX` bytes.
+impl<S, const BLOCK_SIZE: usize> SerializeWith<Vec<String>, S> for AsFrontCodedStrings<BLOCK_SIZE>
+where
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    fn serialize_with(field: &Vec<String>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let () = Self::VALID_BLOCK_SIZE;
+        assert!(
+            field.len() <= u32::MAX as usize,
+            "AsFrontCodedStrings supports at most u32::MAX strings"
+        );
+
+        unsafe {
+            // Measure every block first so the encoded strings can be written in one go
+            let mut blocks =
+                ScratchVec::new(serializer, (field.len() + BLOCK_SIZE - 1) / BLOCK_SIZE)?;
+            let mut data_len = 0;
+            for (i, s) in field.iter().enumerate() {
+                let shared = if i % BLOCK_SIZE == 0 {
+                    blocks.push(data_len as u32);
+                    0
+                } else {
+                    let shared = shared_prefix(&field[i - 1], s);
+                    data_len += varint::encoded_len(shared as u64);
+                    shared
+                };
+                let suffix_len = s.len() - shared;
+                data_len += varint::encoded_len(suffix_len as u64) + suffix_len;
+            }
+            assert!(
+                data_len <= u32::MAX as usize,
+                "AsFrontCodedStrings supports at most u32::MAX bytes of encoded strings"
+            );
+
+            let mut data = ScratchVec::<u8>::new(serializer, data_len)?;
+            for (i, s) in field.iter().enumerate() {
+                let shared = if i % BLOCK_SIZE == 0 {
+                    0
+                } else {
+                    let shared = shared_prefix(&field[i - 1], s);
+                    varint::encode(shared as u64, |byte| data.push(byte));
+                    shared
+                };
+                let suffix = &s.as_bytes()[shared..];
+                varint::encode(suffix.len() as u64, |byte| data.push(byte));
+                for &byte in suffix {
+                    data.push(byte);
+                }
+            }
+
+            let resolver = FrontCodedStringsResolver {
+                blocks_len: blocks.len(),
+                blocks: ArchivedVec::serialize_from_slice(&blocks, serializer)?,
+                data_len: data.len(),
+                data: ArchivedVec::serialize_from_slice(&data, serializer)?,
+            };
+
+            data.free(serializer)?;
+            blocks.free(serializer)?;
+
+            Ok(resolver)
+        }
+    }
+}
+
+impl<D: Fallible + ?Sized, const BLOCK_SIZE: usize>
+    DeserializeWith<ArchivedFrontCodedStrings, Vec<String>, D> for AsFrontCodedStrings<BLOCK_SIZE>
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedFrontCodedStrings,
+        _: &mut D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(field.iter().collect())
+    }
+}
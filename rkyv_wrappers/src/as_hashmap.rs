@@ -1,12 +1,20 @@
 //! A wrapper that converts a `Vec` to an `ArchivedHashMap` at serialization time.
 
 use rkyv::{
-    ser::{ScratchSpace, Serializer},
     collections::hash_map::{ArchivedHashMap, HashMapResolver},
+    ser::{ScratchSpace, Serializer},
     with::{ArchiveWith, DeserializeWith, SerializeWith},
-    Archive, Deserialize, Fallible, Serialize,
+    Archive, Archived, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::{
+    borrow::Cow,
+    collections::{hash_map::DefaultHasher, BTreeMap, HashMap, VecDeque},
+    convert::TryInto,
+    error::Error,
+    fmt,
+    hash::{BuildHasher, Hash, Hasher},
+    marker::PhantomData,
 };
-use std::hash::Hash;
 
 /// A wrapper that attempts to convert a vector to and from `ArchivedHashMap`
 ///
@@ -15,6 +23,12 @@ use std::hash::Hash;
 /// unnecessary hashes that will never be used. By labeling a vector `AsHashMap`, you can use its
 /// archived version just like `ArchivedHashMap` without having costy `HashMap` creations.
 ///
+/// Besides `Vec<(K, V)>`, any container that implements [`Entries`] can be labeled `AsHashMap`, and
+/// containers that implement [`FromEntries`] can be deserialized from it.
+///
+/// With the `validation` feature, the archived map is checked like any other `ArchivedHashMap`,
+/// including that every key is found at the position the hash index expects.
+///
 /// Example:
 ///
 /// ```rust
@@ -45,13 +59,326 @@ use std::hash::Hash;
 /// ```
 pub struct AsHashMap;
 
-impl<K: Archive, V: Archive> ArchiveWith<Vec<(K, V)>> for AsHashMap {
-    type Archived = ArchivedHashMap<K::Archived, V::Archived>;
+/// A container of key-value pairs that can be serialized with [`AsHashMap`] and
+/// [`AsHashMapDedup`].
+///
+/// This is implemented for `Vec`, boxed slices, arrays, `Cow` slices, `VecDeque`, and `SmallVec`
+/// (with the `smallvec` feature). Implement it for your own containers to use them with these
+/// wrappers, along with [`FromEntries`] to deserialize them.
+pub trait Entries {
+    /// The type of the keys in the container
+    type Key;
+    /// The type of the values in the container
+    type Value;
+
+    /// Returns the number of entries in the container.
+    fn len(&self) -> usize;
+
+    /// Returns `true` if the container has no entries.
+    #[inline]
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Returns the key and value of the entry at the given index.
+    ///
+    /// `index` is always less than [`len`](Entries::len).
+    fn entry(&self, index: usize) -> (&Self::Key, &Self::Value);
+}
+
+/// A collection that can be built from the entries of an archived hash map.
+///
+/// This is implemented for all of the containers that implement [`Entries`], so fields
+/// deserialize back into the same type they were archived from. It is also implemented for
+/// `HashMap` with any `BuildHasher`, `BTreeMap`, and `IndexMap` (with the `indexmap` feature), so
+/// an archived map can be deserialized directly into an owned map by calling
+/// [`DeserializeWith::deserialize_with`] on the wrapper.
+///
+/// `D` is the deserializer, so that collections that can't hold every archived map can report it
+/// through the deserializer's error type. Arrays return an [`EntryCountError`] if the archived map
+/// doesn't have exactly `N` entries, which can happen if duplicate keys were merged during
+/// serialization or the buffer was written by someone else. Deserializing into an array requires
+/// a deserializer whose error type implements `From<EntryCountError>`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, with::DeserializeWith, Infallible};
+/// use rkyv_wrappers::as_hashmap::AsHashMap;
+/// use std::collections::{BTreeMap, HashMap};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+/// struct StructWithHashMap {
+///     #[with(AsHashMap)]
+///     pub hash_map: Vec<(u32, String)>,
+/// }
+/// let original = StructWithHashMap {
+///     hash_map: vec![(1, String::from("a")), (2, String::from("b"))]
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithHashMap>(&buffer)
+/// };
+/// let hash_map: HashMap<u32, String> =
+///     AsHashMap::deserialize_with(&output.hash_map, &mut Infallible).unwrap();
+/// assert_eq!(hash_map[&1], "a");
+/// let btree_map: BTreeMap<u32, String> =
+///     AsHashMap::deserialize_with(&output.hash_map, &mut Infallible).unwrap();
+/// assert_eq!(btree_map.keys().copied().collect::<Vec<_>>(), vec![1, 2]);
+/// ```
+pub trait FromEntries<D: Fallible + ?Sized>: Sized {
+    /// The type of the keys in the collection
+    type Key;
+    /// The type of the values in the collection
+    type Value;
+
+    /// Builds the collection from the deserialized entries.
+    fn from_entries(entries: Vec<(Self::Key, Self::Value)>) -> Result<Self, D::Error>;
+}
+
+/// An error returned when an archived collection doesn't have as many entries as the array it's
+/// deserialized into.
+///
+/// This is returned when deserializing arrays from the archived maps of this module and from an
+/// [`ArchivedBitVec`](crate::as_bitvec::ArchivedBitVec).
+#[derive(Debug)]
+pub struct EntryCountError {
+    /// The number of entries the collection holds
+    pub expected: usize,
+    /// The number of entries in the archived collection
+    pub found: usize,
+}
+
+impl fmt::Display for EntryCountError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "expected {} entries in archived collection, found {}",
+            self.expected, self.found,
+        )
+    }
+}
+
+impl Error for EntryCountError {}
+
+impl<K, V> Entries for Vec<(K, V)> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn len(&self) -> usize {
+        self.as_slice().len()
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> (&K, &V) {
+        let (k, v) = &self[index];
+        (k, v)
+    }
+}
+
+impl<K, V, D: Fallible + ?Sized> FromEntries<D> for Vec<(K, V)> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(entries)
+    }
+}
+
+impl<K, V> Entries for Box<[(K, V)]> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn len(&self) -> usize {
+        <[(K, V)]>::len(self)
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> (&K, &V) {
+        let (k, v) = &self[index];
+        (k, v)
+    }
+}
+
+impl<K, V, D: Fallible + ?Sized> FromEntries<D> for Box<[(K, V)]> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(entries.into_boxed_slice())
+    }
+}
+
+impl<K, V, const N: usize> Entries for [(K, V); N] {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn len(&self) -> usize {
+        N
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> (&K, &V) {
+        let (k, v) = &self[index];
+        (k, v)
+    }
+}
+
+impl<K, V, D, const N: usize> FromEntries<D> for [(K, V); N]
+where
+    D: Fallible + ?Sized,
+    D::Error: From<EntryCountError>,
+{
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        let found = entries.len();
+        entries
+            .try_into()
+            .map_err(|_| EntryCountError { expected: N, found }.into())
+    }
+}
+
+impl<K: Clone, V: Clone> Entries for Cow<'_, [(K, V)]> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn len(&self) -> usize {
+        <[(K, V)]>::len(self)
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> (&K, &V) {
+        let (k, v) = &self[index];
+        (k, v)
+    }
+}
+
+impl<K: Clone, V: Clone, D: Fallible + ?Sized> FromEntries<D> for Cow<'_, [(K, V)]> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(Cow::Owned(entries))
+    }
+}
+
+impl<K, V> Entries for VecDeque<(K, V)> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn len(&self) -> usize {
+        VecDeque::len(self)
+    }
+
+    #[inline]
+    fn entry(&self, index: usize) -> (&K, &V) {
+        let (k, v) = &self[index];
+        (k, v)
+    }
+}
+
+impl<K, V, D: Fallible + ?Sized> FromEntries<D> for VecDeque<(K, V)> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(entries.into())
+    }
+}
+
+#[cfg(feature = "smallvec")]
+const _: () = {
+    use smallvec::{Array, SmallVec};
+
+    impl<K, V, A: Array<Item = (K, V)>> Entries for SmallVec<A> {
+        type Key = K;
+        type Value = V;
+
+        #[inline]
+        fn len(&self) -> usize {
+            SmallVec::len(self)
+        }
+
+        #[inline]
+        fn entry(&self, index: usize) -> (&K, &V) {
+            let (k, v) = &self[index];
+            (k, v)
+        }
+    }
+
+    impl<K, V, A: Array<Item = (K, V)>, D: Fallible + ?Sized> FromEntries<D> for SmallVec<A> {
+        type Key = K;
+        type Value = V;
+
+        #[inline]
+        fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+            Ok(SmallVec::from_vec(entries))
+        }
+    }
+};
+
+impl<K: Hash + Eq, V, S: BuildHasher + Default, D: Fallible + ?Sized> FromEntries<D>
+    for HashMap<K, V, S>
+{
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(entries.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V, D: Fallible + ?Sized> FromEntries<D> for BTreeMap<K, V> {
+    type Key = K;
+    type Value = V;
+
+    #[inline]
+    fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+        Ok(entries.into_iter().collect())
+    }
+}
+
+#[cfg(feature = "indexmap")]
+const _: () = {
+    use indexmap::IndexMap;
+
+    impl<K: Hash + Eq, V, S: BuildHasher + Default, D: Fallible + ?Sized> FromEntries<D>
+        for IndexMap<K, V, S>
+    {
+        type Key = K;
+        type Value = V;
+
+        #[inline]
+        fn from_entries(entries: Vec<(K, V)>) -> Result<Self, D::Error> {
+            Ok(entries.into_iter().collect())
+        }
+    }
+};
+
+impl<F: Entries> ArchiveWith<F> for AsHashMap
+where
+    F::Key: Archive,
+    F::Value: Archive,
+{
+    type Archived = ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>;
     type Resolver = HashMapResolver;
 
     #[inline]
     unsafe fn resolve_with(
-        field: &Vec<(K, V)>,
+        field: &F,
         pos: usize,
         resolver: Self::Resolver,
         out: *mut Self::Archived,
@@ -60,38 +387,358 @@ impl<K: Archive, V: Archive> ArchiveWith<Vec<(K, V)>> for AsHashMap {
     }
 }
 
-impl<
-        K: Archive + Serialize<S> + Hash + Eq,
-        V: Archive + Serialize<S>,
-        S: ScratchSpace + Serializer + Fallible + ?Sized,
-    > SerializeWith<Vec<(K, V)>, S> for AsHashMap
+impl<F: Entries, S: ScratchSpace + Serializer + Fallible + ?Sized> SerializeWith<F, S> for AsHashMap
+where
+    F::Key: Serialize<S> + Hash + Eq,
+    F::Value: Serialize<S>,
 {
     #[inline]
-    fn serialize_with(field: &Vec<(K, V)>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
-        // The user must guarantee that the vector contains unique keys
+    fn serialize_with(field: &F, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        // The user must guarantee that the container has unique keys
         unsafe {
             ArchivedHashMap::serialize_from_iter(
-                field.iter().map(|(x, y)| (x, y)),
+                (0..field.len()).map(|i| field.entry(i)),
                 serializer,
             )
         }
     }
 }
 
-impl<K: Archive, V: Archive, D: Fallible + ?Sized>
-    DeserializeWith<ArchivedHashMap<K::Archived, V::Archived>, Vec<(K, V)>, D> for AsHashMap
+impl<F: FromEntries<D>, D: Fallible + ?Sized>
+    DeserializeWith<ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>, F, D> for AsHashMap
 where
-    K::Archived: Deserialize<K, D>,
-    V::Archived: Deserialize<V, D>,
+    F::Key: Archive,
+    F::Value: Archive,
+    Archived<F::Key>: Deserialize<F::Key, D>,
+    Archived<F::Value>: Deserialize<F::Value, D>,
 {
     #[inline]
     fn deserialize_with(
-        field: &ArchivedHashMap<K::Archived, V::Archived>,
+        field: &ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>,
         deserializer: &mut D,
-    ) -> Result<Vec<(K, V)>, D::Error> {
-        field
+    ) -> Result<F, D::Error> {
+        let entries = field
             .iter()
             .map(|(k, v)| Ok((k.deserialize(deserializer)?, v.deserialize(deserializer)?)))
-            .collect()
+            .collect::<Result<Vec<_>, _>>()?;
+        F::from_entries(entries)
+    }
+}
+
+/// A wrapper like [`AsHashMap`] that handles duplicate keys according to a [`DuplicateKeyPolicy`].
+///
+/// `AsHashMap` relies on the caller to guarantee that every key in the vector is unique and will
+/// produce a corrupted archive if that is not the case. `AsHashMapDedup` instead finds repeated
+/// keys with a temporary hash table allocated in the serializer's scratch space and lets the policy
+/// decide what to do with them:
+///
+/// - [`RejectDuplicates`] fails serialization with a [`DuplicateKeyError`]. The serializer's error
+///   type must implement `From<DuplicateKeyError>`.
+/// - [`FirstWins`] keeps the value of the first entry with each key.
+/// - [`LastWins`] keeps the value of the last entry with each key, like `HashMap::from_iter`.
+///
+/// The archived type is the same `ArchivedHashMap` produced by `AsHashMap`, and deserializing it
+/// produces one entry per unique key.
+///
+/// With the `validation` feature, the archived map is checked like any other `ArchivedHashMap`,
+/// which rejects archives that contain the same key more than once.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_hashmap::{AsHashMapDedup, LastWins};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+/// struct StructWithHashMap {
+///     #[with(AsHashMapDedup<LastWins>)]
+///     pub hash_map: Vec<(u32, String)>,
+/// }
+/// let original = StructWithHashMap {
+///     hash_map: vec![(1, String::from("a")), (2, String::from("b")), (1, String::from("c"))]
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithHashMap>(&buffer)
+/// };
+/// assert_eq!(output.hash_map.len(), 2);
+/// assert_eq!(output.hash_map.get(&1).unwrap(), &"c");
+/// let deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.hash_map.len(), 2);
+/// ```
+pub struct AsHashMapDedup<P> {
+    _policy: PhantomData<P>,
+}
+
+/// A wrapper like [`AsHashMap`] that fails serialization if the vector contains duplicate keys.
+///
+/// See [`AsHashMapDedup`] and [`RejectDuplicates`] for details.
+pub type AsHashMapChecked = AsHashMapDedup<RejectDuplicates>;
+
+/// A wrapper for static lookup tables that archives a vector as a minimal perfect hash map.
+///
+/// The index of every `ArchivedHashMap` is already a minimal perfect hash built with
+/// [compress, hash and displace](http://cmph.sourceforge.net/papers/esa09.pdf): `get` hashes the
+/// key into the displacement table, then hashes it at most once more to find the only entry that
+/// could hold it, and compares that single entry's key. There are no collision chains or probe
+/// sequences, so a lookup touches one displacement and one entry no matter how large the map is.
+///
+/// Building the displacement table searches for a seed that sends every key of a bucket to a free
+/// slot. That can never succeed for two equal keys, so the search tries all 2^31 seeds before
+/// giving up and leaves the map corrupted. `AsPerfectHashMap` is [`AsHashMapChecked`], so repeated
+/// keys fail serialization with a [`DuplicateKeyError`] instead. The serializer's
+/// error type must implement `From<DuplicateKeyError>`. Use [`AsHashMap`] to skip the check when
+/// the keys are known to be unique.
+pub type AsPerfectHashMap = AsHashMapChecked;
+
+/// Decides how wrappers like [`AsHashMapDedup`] handle entries that repeat an earlier key.
+pub trait DuplicateKeyPolicy<E> {
+    /// Handles the entry at index `duplicate`, whose key was first seen at index `kept.0`.
+    ///
+    /// `kept` holds the index of the entry whose key is archived and the index of the entry whose
+    /// value is archived. Returning an error aborts serialization.
+    fn on_duplicate(kept: &mut (usize, usize), duplicate: usize) -> Result<(), E>;
+}
+
+/// A [`DuplicateKeyPolicy`] that fails with a [`DuplicateKeyError`] on the first repeated key.
+pub struct RejectDuplicates;
+
+impl<E: From<DuplicateKeyError>> DuplicateKeyPolicy<E> for RejectDuplicates {
+    #[inline]
+    fn on_duplicate(kept: &mut (usize, usize), duplicate: usize) -> Result<(), E> {
+        Err(DuplicateKeyError {
+            first: kept.0,
+            duplicate,
+        }
+        .into())
+    }
+}
+
+/// A [`DuplicateKeyPolicy`] that keeps the value of the first entry with each key.
+pub struct FirstWins;
+
+impl<E> DuplicateKeyPolicy<E> for FirstWins {
+    #[inline]
+    fn on_duplicate(_: &mut (usize, usize), _: usize) -> Result<(), E> {
+        Ok(())
+    }
+}
+
+/// A [`DuplicateKeyPolicy`] that keeps the value of the last entry with each key.
+pub struct LastWins;
+
+impl<E> DuplicateKeyPolicy<E> for LastWins {
+    #[inline]
+    fn on_duplicate(kept: &mut (usize, usize), duplicate: usize) -> Result<(), E> {
+        kept.1 = duplicate;
+        Ok(())
+    }
+}
+
+/// The resolver for [`AsHashMapDedup`].
+pub struct DedupResolver {
+    len: usize,
+    inner: HashMapResolver,
+}
+
+/// An error returned by [`RejectDuplicates`] when a vector contains a repeated key.
+#[derive(Debug)]
+pub struct DuplicateKeyError {
+    /// The index of the first entry with the repeated key
+    pub first: usize,
+    /// The index of the entry that repeated the key
+    pub duplicate: usize,
+}
+
+impl fmt::Display for DuplicateKeyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "duplicate key in hash map entries: entry {} has the same key as entry {}",
+            self.duplicate, self.first,
+        )
+    }
+}
+
+impl Error for DuplicateKeyError {}
+
+/// Finds the unique keys among `len` entries using a hash table allocated in scratch space.
+///
+/// Returns one `(key_index, value_index)` pair per unique key in order of first appearance. Both
+/// indices start out as the index of the first entry with that key. `on_duplicate` is called with
+/// that pair and the index of every later entry that repeats the key.
+///
+/// # Safety
+///
+/// The returned scratch vec must be freed with the same serializer before any scratch space
+/// allocated earlier is freed.
+pub(crate) unsafe fn unique_entries<'a, K, S, F>(
+    len: usize,
+    key: impl Fn(usize) -> &'a K,
+    serializer: &mut S,
+    mut on_duplicate: F,
+) -> Result<ScratchVec<(usize, usize)>, S::Error>
+where
+    K: 'a + Hash + Eq + ?Sized,
+    S: ScratchSpace + ?Sized,
+    F: FnMut(&mut (usize, usize), usize) -> Result<(), S::Error>,
+{
+    const EMPTY: usize = usize::MAX;
+
+    let mut unique = ScratchVec::new(serializer, len)?;
+
+    // Keep the load factor at or below one half so probe sequences stay short
+    let capacity = (len * 2).next_power_of_two();
+    let mask = capacity - 1;
+    let mut table = match ScratchVec::new(serializer, capacity) {
+        Ok(table) => table,
+        Err(e) => {
+            unique.free(serializer)?;
+            return Err(e);
+        }
+    };
+    for _ in 0..capacity {
+        table.push(EMPTY);
+    }
+
+    for i in 0..len {
+        let k = key(i);
+        let mut hasher = DefaultHasher::new();
+        k.hash(&mut hasher);
+        let mut slot = hasher.finish() as usize & mask;
+        loop {
+            let u = table[slot];
+            if u == EMPTY {
+                table[slot] = unique.len();
+                unique.push((i, i));
+                break;
+            } else if key(unique[u].0) == k {
+                if let Err(e) = on_duplicate(&mut unique[u], i) {
+                    table.free(serializer)?;
+                    unique.free(serializer)?;
+                    return Err(e);
+                }
+                break;
+            }
+            slot = (slot + 1) & mask;
+        }
+    }
+
+    table.free(serializer)?;
+    Ok(unique)
+}
+
+/// Finds the unique keys among `len` entries by sorting their indices in scratch space.
+///
+/// Returns one `(key_index, value_index)` pair per unique key in key order. Both indices start out
+/// as the index of the first entry with that key. `on_duplicate` is called with that pair and the
+/// index of every later entry that repeats the key, in the order the entries appear.
+///
+/// # Safety
+///
+/// The returned scratch vec must be freed with the same serializer before any scratch space
+/// allocated earlier is freed.
+pub(crate) unsafe fn sorted_entries<'a, K, S, F>(
+    len: usize,
+    key: impl Fn(usize) -> &'a K,
+    serializer: &mut S,
+    mut on_duplicate: F,
+) -> Result<ScratchVec<(usize, usize)>, S::Error>
+where
+    K: 'a + Ord + ?Sized,
+    S: ScratchSpace + ?Sized,
+    F: FnMut(&mut (usize, usize), usize) -> Result<(), S::Error>,
+{
+    let mut unique = ScratchVec::<(usize, usize)>::new(serializer, len)?;
+
+    let mut order = match ScratchVec::new(serializer, len) {
+        Ok(order) => order,
+        Err(e) => {
+            unique.free(serializer)?;
+            return Err(e);
+        }
+    };
+    for i in 0..len {
+        order.push(i);
+    }
+    // Sorting by index within equal keys keeps repeated keys in their original order
+    order.sort_unstable_by(|&a, &b| key(a).cmp(key(b)).then(a.cmp(&b)));
+
+    for &i in order.iter() {
+        match unique.last_mut() {
+            Some(kept) if key(kept.0) == key(i) => {
+                if let Err(e) = on_duplicate(kept, i) {
+                    order.free(serializer)?;
+                    unique.free(serializer)?;
+                    return Err(e);
+                }
+            }
+            _ => unique.push((i, i)),
+        }
+    }
+
+    order.free(serializer)?;
+    Ok(unique)
+}
+
+impl<F: Entries, P> ArchiveWith<F> for AsHashMapDedup<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+{
+    type Archived = ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>;
+    type Resolver = DedupResolver;
+
+    #[inline]
+    unsafe fn resolve_with(_: &F, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        ArchivedHashMap::resolve_from_len(resolver.len, pos, resolver.inner, out);
+    }
+}
+
+impl<F, S, P> SerializeWith<F, S> for AsHashMapDedup<P>
+where
+    F: Entries,
+    F::Key: Serialize<S> + Hash + Eq,
+    F::Value: Serialize<S>,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    P: DuplicateKeyPolicy<S::Error>,
+{
+    #[inline]
+    fn serialize_with(field: &F, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        unsafe {
+            let entries = unique_entries(
+                field.len(),
+                |i| field.entry(i).0,
+                serializer,
+                P::on_duplicate,
+            )?;
+            let inner = ArchivedHashMap::serialize_from_iter(
+                entries
+                    .iter()
+                    .map(|&(k, v)| (field.entry(k).0, field.entry(v).1)),
+                serializer,
+            )?;
+            let len = entries.len();
+            entries.free(serializer)?;
+            Ok(DedupResolver { len, inner })
+        }
+    }
+}
+
+impl<F: FromEntries<D>, D: Fallible + ?Sized, P>
+    DeserializeWith<ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>, F, D>
+    for AsHashMapDedup<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+    Archived<F::Key>: Deserialize<F::Key, D>,
+    Archived<F::Value>: Deserialize<F::Value, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedHashMap<Archived<F::Key>, Archived<F::Value>>,
+        deserializer: &mut D,
+    ) -> Result<F, D::Error> {
+        AsHashMap::deserialize_with(field, deserializer)
     }
 }
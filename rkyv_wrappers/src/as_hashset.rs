@@ -0,0 +1,155 @@
+//! A wrapper that converts a `Vec` to an `ArchivedHashSet` at serialization time.
+
+use crate::as_hashmap::{unique_entries, DuplicateKeyError, DuplicateKeyPolicy, RejectDuplicates};
+use rkyv::{
+    collections::hash_set::{ArchivedHashSet, HashSetResolver},
+    ser::{ScratchSpace, Serializer},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Deserialize, Fallible, Serialize,
+};
+use std::hash::Hash;
+
+/// A wrapper that attempts to convert a vector to and from `ArchivedHashSet`
+///
+/// Like [`AsHashMap`](crate::as_hashmap::AsHashMap), this avoids building a `HashSet` only to
+/// rehash all of its elements while archiving it. The archived version can be used just like
+/// `ArchivedHashSet`.
+///
+/// The user must guarantee that the vector contains unique elements. Use [`AsHashSetChecked`] to
+/// check for duplicates during serialization instead.
+///
+/// With the `validation` feature, the archived set is checked like any other `ArchivedHashSet`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{
+///     archived_root,
+///     ser::{Serializer, serializers::AllocSerializer},
+///     AlignedVec,
+///     Deserialize,
+///     Infallible,
+/// };
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithHashSet {
+///     #[with(rkyv_wrappers::as_hashset::AsHashSet)]
+///     pub hash_set: Vec<String>,
+/// }
+/// let mut serializer = AllocSerializer::<4096>::default();
+/// let original = StructWithHashSet {
+///     hash_set: vec![String::from("a"), String::from("b")]
+/// };
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_serializer().into_inner();
+/// let output = unsafe {
+///     archived_root::<StructWithHashSet>(&buffer)
+/// };
+/// assert!(output.hash_set.contains("a"));
+/// assert!(!output.hash_set.contains("c"));
+/// let mut deserialized: StructWithHashSet = output.deserialize(&mut Infallible).unwrap();
+/// deserialized.hash_set.sort();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsHashSet;
+
+impl<T: Archive> ArchiveWith<Vec<T>> for AsHashSet {
+    type Archived = ArchivedHashSet<T::Archived>;
+    type Resolver = HashSetResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        ArchivedHashSet::resolve_from_len(field.len(), pos, resolver, out);
+    }
+}
+
+impl<T, S> SerializeWith<Vec<T>, S> for AsHashSet
+where
+    T: Archive + Serialize<S> + Hash + Eq,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    #[inline]
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        // The user must guarantee that the vector contains unique elements
+        unsafe { ArchivedHashSet::serialize_from_iter(field.iter(), serializer) }
+    }
+}
+
+impl<T: Archive, D: Fallible + ?Sized> DeserializeWith<ArchivedHashSet<T::Archived>, Vec<T>, D>
+    for AsHashSet
+where
+    T::Archived: Deserialize<T, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedHashSet<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<T>, D::Error> {
+        field.iter().map(|x| x.deserialize(deserializer)).collect()
+    }
+}
+
+/// A wrapper like [`AsHashSet`] that checks the vector for duplicate elements during
+/// serialization.
+///
+/// Repeated elements are found with a temporary hash table allocated in the serializer's scratch
+/// space and fail serialization with a [`DuplicateKeyError`]. The serializer's error type must
+/// implement `From<DuplicateKeyError>`.
+///
+/// The archived type is the same `ArchivedHashSet` produced by `AsHashSet`.
+pub struct AsHashSetChecked;
+
+impl<T: Archive> ArchiveWith<Vec<T>> for AsHashSetChecked {
+    type Archived = ArchivedHashSet<T::Archived>;
+    type Resolver = HashSetResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        // Serialization fails on duplicate elements, so every element of the vector was serialized
+        AsHashSet::resolve_with(field, pos, resolver, out);
+    }
+}
+
+impl<T, S> SerializeWith<Vec<T>, S> for AsHashSetChecked
+where
+    T: Archive + Serialize<S> + Hash + Eq,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    S::Error: From<DuplicateKeyError>,
+{
+    #[inline]
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        unsafe {
+            let unique = unique_entries(
+                field.len(),
+                |i| &field[i],
+                serializer,
+                <RejectDuplicates as DuplicateKeyPolicy<S::Error>>::on_duplicate,
+            )?;
+            unique.free(serializer)?;
+            ArchivedHashSet::serialize_from_iter(field.iter(), serializer)
+        }
+    }
+}
+
+impl<T: Archive, D: Fallible + ?Sized> DeserializeWith<ArchivedHashSet<T::Archived>, Vec<T>, D>
+    for AsHashSetChecked
+where
+    T::Archived: Deserialize<T, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedHashSet<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<T>, D::Error> {
+        AsHashSet::deserialize_with(field, deserializer)
+    }
+}
@@ -0,0 +1,460 @@
+//! A wrapper that converts a `Vec` to an insertion-ordered archived map at serialization time.
+
+use crate::{
+    archived::to_usize,
+    as_hashmap::{unique_entries, DuplicateKeyPolicy, Entries, FromEntries, LastWins},
+};
+#[cfg(feature = "validation")]
+use rkyv::bytecheck::CheckBytes;
+use rkyv::{
+    collections::hash_map::{ArchivedHashMap, HashMapResolver},
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::{borrow::Borrow, fmt, hash::Hash, iter::FusedIterator, marker::PhantomData, slice};
+
+/// A wrapper that converts a vector to and from an [`ArchivedIndexMap`], which keeps the entries in
+/// their original order.
+///
+/// Iterating an `ArchivedHashMap` visits its entries in an order unrelated to the source vector.
+/// `AsIndexMap` archives the entries in their original order alongside an `ArchivedHashMap` from
+/// each key to its position, so the archived map supports both fast lookups by key and ordered
+/// iteration. Deserializing returns the entries in exactly the order they were serialized.
+///
+/// Every key is stored twice: once in the index and once in the ordered entries. Entries that
+/// repeat a key are handled by the [`DuplicateKeyPolicy`] `P`. The default, [`LastWins`], matches
+/// collecting the vector into an `IndexMap`: each key keeps the position of its first entry and
+/// the value of its last one.
+///
+/// Any container that implements [`Entries`] can be labeled `AsIndexMap`, and any collection that
+/// implements [`FromEntries`] can be deserialized from it.
+///
+/// With the `validation` feature, checking an `ArchivedIndexMap` checks the index and the entries
+/// and then verifies that they agree: both must have the same length, and the index must map the
+/// key of every entry to that entry's position. This guarantees that positions returned by the
+/// index are always in bounds.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithIndexMap {
+///     #[with(rkyv_wrappers::as_indexmap::AsIndexMap)]
+///     pub index_map: Vec<(u32, String)>,
+/// }
+/// let original = StructWithIndexMap {
+///     index_map: vec![(3, String::from("c")), (1, String::from("a")), (2, String::from("b"))]
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithIndexMap>(&buffer)
+/// };
+/// assert_eq!(output.index_map.get(&1).unwrap(), &"a");
+/// let (key, value) = output.index_map.get_index(0).unwrap();
+/// assert_eq!((*key, value.as_str()), (3, "c"));
+/// assert_eq!(output.index_map.keys().copied().collect::<Vec<_>>(), vec![3, 1, 2]);
+/// let deserialized: StructWithIndexMap = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+///
+/// # Panics
+///
+/// Serializing panics if the container has more than `u32::MAX` unique keys.
+pub struct AsIndexMap<P = LastWins> {
+    _policy: PhantomData<P>,
+}
+
+/// An archived map that preserves the order of its entries.
+///
+/// This is the archived version of vectors labeled [`AsIndexMap`].
+#[repr(C)]
+pub struct ArchivedIndexMap<K, V> {
+    index: ArchivedHashMap<K, Archived<u32>>,
+    entries: ArchivedVec<Entry<K, V>>,
+}
+
+/// An entry of an [`ArchivedIndexMap`].
+#[derive(Debug)]
+#[cfg_attr(feature = "validation", derive(CheckBytes))]
+#[cfg_attr(feature = "validation", check_bytes(crate = "rkyv::bytecheck"))]
+#[repr(C)]
+pub struct Entry<K, V> {
+    /// The key of the entry
+    pub key: K,
+    /// The value of the entry
+    pub value: V,
+}
+
+impl<K, V> ArchivedIndexMap<K, V> {
+    /// Gets the number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the map contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the position of the entry with the given key.
+    #[inline]
+    pub fn get_index_of<Q>(&self, k: &Q) -> Option<usize>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.get(k).map(|&i| to_usize(i))
+    }
+
+    /// Gets the position, key, and value of the entry with the given key.
+    #[inline]
+    pub fn get_full<Q>(&self, k: &Q) -> Option<(usize, &K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        let index = self.get_index_of(k)?;
+        let entry = &self.entries[index];
+        Some((index, &entry.key, &entry.value))
+    }
+
+    /// Gets the value associated with the given key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.get_full(k).map(|(_, _, v)| v)
+    }
+
+    /// Returns whether a key is present in the map.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Hash + Eq + ?Sized,
+    {
+        self.index.contains_key(k)
+    }
+
+    /// Gets the key and value of the entry at the given position.
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries
+            .get(index)
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Gets the entries of the map in order.
+    #[inline]
+    pub fn as_slice(&self) -> &[Entry<K, V>] {
+        self.entries.as_slice()
+    }
+
+    /// Gets an iterator over the entries of the map in order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// Gets an iterator over the keys of the map in order.
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.entries.iter().map(|entry| &entry.key)
+    }
+
+    /// Gets an iterator over the values of the map in order.
+    #[inline]
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|entry| &entry.value)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ArchivedIndexMap<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a ArchivedIndexMap<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an [`ArchivedIndexMap`] in order.
+pub struct Iter<'a, K, V> {
+    inner: slice::Iter<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &entry.value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// Errors that can occur while checking an [`ArchivedIndexMap`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum IndexMapError<I, E> {
+    /// An error occurred while checking the index
+    IndexError(I),
+    /// An error occurred while checking the entries
+    EntriesError(E),
+    /// The index and the entries have different lengths
+    LengthMismatch {
+        /// The number of keys in the index
+        index_len: usize,
+        /// The number of entries
+        entries_len: usize,
+    },
+    /// The index does not map the key of an entry to that entry's position
+    InvalidPosition {
+        /// The position of the entry
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<I: fmt::Display, E: fmt::Display> fmt::Display for IndexMapError<I, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IndexMapError::IndexError(e) => write!(f, "index check error: {}", e),
+            IndexMapError::EntriesError(e) => write!(f, "entries check error: {}", e),
+            IndexMapError::LengthMismatch {
+                index_len,
+                entries_len,
+            } => write!(
+                f,
+                "length mismatch: index has {} keys but there are {} entries",
+                index_len, entries_len,
+            ),
+            IndexMapError::InvalidPosition { index } => {
+                write!(f, "invalid position in index: for entry {}", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<I, E> std::error::Error for IndexMapError<I, E>
+where
+    I: std::error::Error + 'static,
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            IndexMapError::IndexError(e) => Some(e as &dyn std::error::Error),
+            IndexMapError::EntriesError(e) => Some(e as &dyn std::error::Error),
+            IndexMapError::LengthMismatch { .. } | IndexMapError::InvalidPosition { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::validation::ArchiveContext;
+    use std::ptr;
+
+    impl<K, V, C> CheckBytes<C> for ArchivedIndexMap<K, V>
+    where
+        K: CheckBytes<C> + Eq + Hash,
+        V: CheckBytes<C>,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = IndexMapError<
+            <ArchivedHashMap<K, Archived<u32>> as CheckBytes<C>>::Error,
+            <ArchivedVec<Entry<K, V>> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            // Subtrees have to be checked in the order they were serialized, and the entries are
+            // serialized before the index
+            let entries = ArchivedVec::check_bytes(ptr::addr_of!((*value).entries), context)
+                .map_err(IndexMapError::EntriesError)?;
+            let index = ArchivedHashMap::check_bytes(ptr::addr_of!((*value).index), context)
+                .map_err(IndexMapError::IndexError)?;
+
+            if index.len() != entries.len() {
+                return Err(IndexMapError::LengthMismatch {
+                    index_len: index.len(),
+                    entries_len: entries.len(),
+                });
+            }
+            let value = &*value;
+            for (i, entry) in entries.iter().enumerate() {
+                if value.get_index_of(&entry.key) != Some(i) {
+                    return Err(IndexMapError::InvalidPosition { index: i });
+                }
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsIndexMap`].
+pub struct IndexMapResolver {
+    len: usize,
+    index: HashMapResolver,
+    entries: VecResolver,
+}
+
+/// A borrowed entry that archives as an [`Entry`].
+pub(crate) struct EntryRef<'a, K, V> {
+    pub(crate) key: &'a K,
+    pub(crate) value: &'a V,
+}
+
+impl<K: Archive, V: Archive> Archive for EntryRef<'_, K, V> {
+    type Archived = Entry<K::Archived, V::Archived>;
+    type Resolver = (K::Resolver, V::Resolver);
+
+    #[inline]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.key);
+        self.key.resolve(pos + fp, resolver.0, fo);
+        let (fp, fo) = out_field!(out.value);
+        self.value.resolve(pos + fp, resolver.1, fo);
+    }
+}
+
+impl<K: Serialize<S>, V: Serialize<S>, S: Fallible + ?Sized> Serialize<S> for EntryRef<'_, K, V> {
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok((
+            self.key.serialize(serializer)?,
+            self.value.serialize(serializer)?,
+        ))
+    }
+}
+
+impl<F: Entries, P> ArchiveWith<F> for AsIndexMap<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+{
+    type Archived = ArchivedIndexMap<Archived<F::Key>, Archived<F::Value>>;
+    type Resolver = IndexMapResolver;
+
+    #[inline]
+    unsafe fn resolve_with(_: &F, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.index);
+        ArchivedHashMap::resolve_from_len(resolver.len, pos + fp, resolver.index, fo);
+        let (fp, fo) = out_field!(out.entries);
+        ArchivedVec::resolve_from_len(resolver.len, pos + fp, resolver.entries, fo);
+    }
+}
+
+impl<F, S, P> SerializeWith<F, S> for AsIndexMap<P>
+where
+    F: Entries,
+    F::Key: Serialize<S> + Hash + Eq,
+    F::Value: Serialize<S>,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    P: DuplicateKeyPolicy<S::Error>,
+{
+    #[inline]
+    fn serialize_with(field: &F, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        unsafe {
+            let unique = unique_entries(
+                field.len(),
+                |i| field.entry(i).0,
+                serializer,
+                P::on_duplicate,
+            )?;
+            let len = unique.len();
+            assert!(
+                len <= u32::MAX as usize,
+                "AsIndexMap supports at most u32::MAX unique keys"
+            );
+
+            let entries =
+                ArchivedVec::serialize_from_iter::<EntryRef<'_, F::Key, F::Value>, _, _, _>(
+                    unique.iter().map(|&(k, v)| EntryRef {
+                        key: field.entry(k).0,
+                        value: field.entry(v).1,
+                    }),
+                    serializer,
+                )?;
+
+            let mut positions = ScratchVec::new(serializer, len)?;
+            for i in 0..len {
+                positions.push(i as u32);
+            }
+            let index = ArchivedHashMap::serialize_from_iter(
+                unique
+                    .iter()
+                    .zip(positions.iter())
+                    .map(|(&(k, _), p)| (field.entry(k).0, p)),
+                serializer,
+            )?;
+            positions.free(serializer)?;
+            unique.free(serializer)?;
+
+            Ok(IndexMapResolver {
+                len,
+                index,
+                entries,
+            })
+        }
+    }
+}
+
+impl<F: FromEntries<D>, D: Fallible + ?Sized, P>
+    DeserializeWith<ArchivedIndexMap<Archived<F::Key>, Archived<F::Value>>, F, D> for AsIndexMap<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+    Archived<F::Key>: Deserialize<F::Key, D>,
+    Archived<F::Value>: Deserialize<F::Value, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedIndexMap<Archived<F::Key>, Archived<F::Value>>,
+        deserializer: &mut D,
+    ) -> Result<F, D::Error> {
+        let entries = field
+            .iter()
+            .map(|(k, v)| Ok((k.deserialize(deserializer)?, v.deserialize(deserializer)?)))
+            .collect::<Result<Vec<_>, _>>()?;
+        F::from_entries(entries)
+    }
+}
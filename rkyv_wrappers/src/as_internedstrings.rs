@@ -0,0 +1,649 @@
+//! A wrapper that dictionary encodes a `Vec` of strings at serialization time.
+
+use crate::{archived::to_usize, as_hashmap::unique_entries};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer, SharedSerializeRegistry},
+    string::{
+        repr::{ArchivedStringRepr, INLINE_CAPACITY},
+        ArchivedString,
+    },
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, ScratchVec, Serialize, SerializeUnsized,
+};
+use std::{
+    alloc::Layout, collections::HashMap, fmt, iter::FusedIterator, ops::Deref, ptr::NonNull, slice,
+};
+
+/// A wrapper that converts a vector of strings to and from an [`ArchivedInternedStrings`], which
+/// stores each distinct string once.
+///
+/// Vectors of low-cardinality strings like country codes and status names repeat the same few
+/// strings over and over. `AsInternedStrings` finds the distinct strings with a temporary hash
+/// table in the serializer's scratch space, archives them once in a table, and replaces every
+/// element with its `u32` index into the table. Strings are added to the table in the order they
+/// first appear.
+///
+/// Each field labeled `AsInternedStrings` has its own table. Use [`AsSharedInternedStrings`] to
+/// also share the bytes of strings between fields.
+///
+/// With the `validation` feature, checking an `ArchivedInternedStrings` checks every string in the
+/// table and verifies that every index is in bounds.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithCountries {
+///     #[with(rkyv_wrappers::as_internedstrings::AsInternedStrings)]
+///     pub countries: Vec<String>,
+/// }
+/// let original = StructWithCountries {
+///     countries: ["US", "FR", "US", "DE", "FR", "US"].iter().map(|s| s.to_string()).collect(),
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithCountries>(&buffer)
+/// };
+/// assert_eq!(output.countries.len(), 6);
+/// assert_eq!(output.countries.strings().len(), 3);
+/// assert_eq!(output.countries.get(3), Some("DE"));
+/// assert_eq!(output.countries.iter().filter(|&c| c == "US").count(), 3);
+/// let deserialized: StructWithCountries = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsInternedStrings;
+
+/// A wrapper like [`AsInternedStrings`] that also shares the bytes of strings between fields.
+///
+/// The serializer must implement [`StringRegistry`], which remembers where the bytes of every
+/// interned string were written. Any later field labeled `AsSharedInternedStrings` that contains
+/// the same string points its table entry at those bytes instead of writing them again. Wrap a
+/// serializer in an [`InterningSerializer`] to add a registry to it.
+///
+/// Strings of up to `INLINE_CAPACITY` bytes are stored inside their table entry, so only longer
+/// strings are shared.
+///
+/// The archived type is the same `ArchivedInternedStrings` produced by `AsInternedStrings`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, ser::{serializers::AllocSerializer, Serializer}};
+/// use rkyv_wrappers::as_internedstrings::{AsSharedInternedStrings, InterningSerializer};
+/// #[derive(rkyv::Archive, rkyv::Serialize)]
+/// struct Orders {
+///     #[with(AsSharedInternedStrings)]
+///     pub open: Vec<String>,
+///     #[with(AsSharedInternedStrings)]
+///     pub closed: Vec<String>,
+/// }
+/// let statuses = vec![String::from("awaiting payment"), String::from("awaiting shipment")];
+/// let original = Orders {
+///     open: statuses.clone(),
+///     closed: statuses,
+/// };
+/// let mut serializer = InterningSerializer::new(AllocSerializer::<4096>::default());
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_inner().into_serializer().into_inner();
+/// let output = unsafe {
+///     archived_root::<Orders>(&buffer)
+/// };
+/// assert_eq!(output.closed.get(1), Some("awaiting shipment"));
+/// assert_eq!(
+///     output.open.strings()[0].as_ptr(),
+///     output.closed.strings()[0].as_ptr(),
+/// );
+/// ```
+pub struct AsSharedInternedStrings;
+
+/// A serializer that can share the bytes of interned strings between fields.
+///
+/// See [`AsSharedInternedStrings`] for details.
+pub trait StringRegistry: Fallible {
+    /// Gets the position of the bytes of a previously added string.
+    ///
+    /// Returns `None` if the string has not been added yet.
+    fn get_string(&self, value: &str) -> Option<usize>;
+
+    /// Adds the position of the bytes of a string to the registry.
+    fn add_string(&mut self, value: &str, pos: usize) -> Result<(), Self::Error>;
+}
+
+/// A serializer adapter that adds a [`StringRegistry`] to another serializer.
+///
+/// `InterningSerializer` implements every serializer trait that the wrapped serializer does.
+#[derive(Debug, Default)]
+pub struct InterningSerializer<S> {
+    inner: S,
+    strings: HashMap<Box<str>, usize>,
+}
+
+impl<S> InterningSerializer<S> {
+    /// Wraps a serializer.
+    #[inline]
+    pub fn new(inner: S) -> Self {
+        Self {
+            inner,
+            strings: HashMap::new(),
+        }
+    }
+
+    /// Returns the wrapped serializer.
+    #[inline]
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S: Fallible> Fallible for InterningSerializer<S> {
+    type Error = S::Error;
+}
+
+impl<S: Serializer> Serializer for InterningSerializer<S> {
+    #[inline]
+    fn pos(&self) -> usize {
+        self.inner.pos()
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        self.inner.write(bytes)
+    }
+}
+
+impl<S: ScratchSpace> ScratchSpace for InterningSerializer<S> {
+    #[inline]
+    unsafe fn push_scratch(&mut self, layout: Layout) -> Result<NonNull<[u8]>, Self::Error> {
+        self.inner.push_scratch(layout)
+    }
+
+    #[inline]
+    unsafe fn pop_scratch(&mut self, ptr: NonNull<u8>, layout: Layout) -> Result<(), Self::Error> {
+        self.inner.pop_scratch(ptr, layout)
+    }
+}
+
+impl<S: SharedSerializeRegistry> SharedSerializeRegistry for InterningSerializer<S> {
+    #[inline]
+    fn get_shared_ptr(&self, value: *const u8) -> Option<usize> {
+        self.inner.get_shared_ptr(value)
+    }
+
+    #[inline]
+    fn add_shared_ptr(&mut self, value: *const u8, pos: usize) -> Result<(), Self::Error> {
+        self.inner.add_shared_ptr(value, pos)
+    }
+}
+
+impl<S: Fallible> StringRegistry for InterningSerializer<S> {
+    #[inline]
+    fn get_string(&self, value: &str) -> Option<usize> {
+        self.strings.get(value).copied()
+    }
+
+    #[inline]
+    fn add_string(&mut self, value: &str, pos: usize) -> Result<(), Self::Error> {
+        self.strings.insert(value.into(), pos);
+        Ok(())
+    }
+}
+
+/// An archived vector of strings that stores each distinct string once.
+///
+/// This is the archived version of vectors labeled [`AsInternedStrings`] and
+/// [`AsSharedInternedStrings`].
+#[repr(C)]
+pub struct ArchivedInternedStrings {
+    strings: ArchivedVec<InternedString>,
+    indices: ArchivedVec<Archived<u32>>,
+}
+
+/// A string in the table of an [`ArchivedInternedStrings`].
+///
+/// This is an `ArchivedString` that may share its bytes with other strings in the archive.
+#[repr(transparent)]
+pub struct InternedString(ArchivedString);
+
+impl InternedString {
+    /// Extracts a string slice containing the entire `InternedString`.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl Deref for InternedString {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl fmt::Debug for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl fmt::Display for InternedString {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl ArchivedInternedStrings {
+    /// Gets the number of strings in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.indices.len()
+    }
+
+    /// Returns whether the vector contains no strings.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// Gets the string at the given position.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&str> {
+        let index = self.indices.get(index)?;
+        Some(self.strings[to_usize(*index)].as_str())
+    }
+
+    /// Gets the table of distinct strings in the order they first appear.
+    #[inline]
+    pub fn strings(&self) -> &[InternedString] {
+        self.strings.as_slice()
+    }
+
+    /// Gets the index into [`strings`](Self::strings) of each string in the vector.
+    #[inline]
+    pub fn indices(&self) -> &[Archived<u32>] {
+        self.indices.as_slice()
+    }
+
+    /// Gets an iterator over the strings in the vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_> {
+        Iter {
+            strings: self.strings.as_slice(),
+            indices: self.indices.iter(),
+        }
+    }
+}
+
+impl fmt::Debug for ArchivedInternedStrings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a> IntoIterator for &'a ArchivedInternedStrings {
+    type Item = &'a str;
+    type IntoIter = Iter<'a>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the strings of an [`ArchivedInternedStrings`].
+pub struct Iter<'a> {
+    strings: &'a [InternedString],
+    indices: slice::Iter<'a, Archived<u32>>,
+}
+
+impl<'a> Iterator for Iter<'a> {
+    type Item = &'a str;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let strings = self.strings;
+        self.indices
+            .next()
+            .map(|&index| strings[to_usize(index)].as_str())
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.indices.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for Iter<'_> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let strings = self.strings;
+        self.indices
+            .next_back()
+            .map(|&index| strings[to_usize(index)].as_str())
+    }
+}
+
+impl ExactSizeIterator for Iter<'_> {}
+impl FusedIterator for Iter<'_> {}
+
+/// Errors that can occur while checking an [`ArchivedInternedStrings`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum InternedStringsError<S, I> {
+    /// An error occurred while checking the table of strings
+    StringsError(S),
+    /// An error occurred while checking the indices
+    IndicesError(I),
+    /// An index is not in the table of strings
+    InvalidIndex {
+        /// The position of the index
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<S: fmt::Display, I: fmt::Display> fmt::Display for InternedStringsError<S, I> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InternedStringsError::StringsError(e) => write!(f, "strings check error: {}", e),
+            InternedStringsError::IndicesError(e) => write!(f, "indices check error: {}", e),
+            InternedStringsError::InvalidIndex { index } => {
+                write!(f, "invalid index: index {} is out of bounds", index)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<S, I> std::error::Error for InternedStringsError<S, I>
+where
+    S: std::error::Error + 'static,
+    I: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            InternedStringsError::StringsError(e) => Some(e as &dyn std::error::Error),
+            InternedStringsError::IndicesError(e) => Some(e as &dyn std::error::Error),
+            InternedStringsError::InvalidIndex { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{
+        bytecheck::CheckBytes,
+        validation::{owned::OwnedPointerError, ArchiveContext},
+    };
+    use std::{error::Error, ptr};
+
+    impl<C: ArchiveContext + ?Sized> CheckBytes<C> for InternedString
+    where
+        C::Error: Error + 'static,
+    {
+        type Error = OwnedPointerError<
+            <ArchivedStringRepr as CheckBytes<C>>::Error,
+            <str as CheckBytes<C>>::Error,
+            C::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let repr = ArchivedStringRepr::check_bytes(value.cast(), context)
+                .map_err(OwnedPointerError::PointerCheckBytesError)?;
+
+            if repr.is_inline() {
+                str::check_bytes(repr.as_str_ptr(), context)
+                    .map_err(OwnedPointerError::ValueCheckBytesError)?;
+            } else {
+                // Shared strings point at the same bytes, so the bytes are only bounds checked
+                // instead of being claimed as a subtree like the bytes of an `ArchivedString`
+                let ptr = context
+                    .check_ptr::<str>(value.cast(), repr.out_of_line_offset(), repr.len())
+                    .map_err(OwnedPointerError::ContextError)?;
+                str::check_bytes(ptr, context).map_err(OwnedPointerError::ValueCheckBytesError)?;
+            }
+
+            Ok(&*value)
+        }
+    }
+
+    impl<C: ArchiveContext + ?Sized> CheckBytes<C> for ArchivedInternedStrings
+    where
+        C::Error: Error + 'static,
+    {
+        type Error = InternedStringsError<
+            <ArchivedVec<InternedString> as CheckBytes<C>>::Error,
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let strings = ArchivedVec::check_bytes(ptr::addr_of!((*value).strings), context)
+                .map_err(InternedStringsError::StringsError)?;
+            let indices = ArchivedVec::check_bytes(ptr::addr_of!((*value).indices), context)
+                .map_err(InternedStringsError::IndicesError)?;
+
+            for (i, &index) in indices.iter().enumerate() {
+                if to_usize(index) >= strings.len() {
+                    return Err(InternedStringsError::InvalidIndex { index: i });
+                }
+            }
+
+            Ok(&*value)
+        }
+    }
+};
+
+/// The resolver for [`AsInternedStrings`] and [`AsSharedInternedStrings`].
+pub struct InternedStringsResolver {
+    strings_len: usize,
+    strings: VecResolver,
+    indices: VecResolver,
+}
+
+/// A string whose bytes were already written, which archives as an [`InternedString`].
+struct InternedStr<'a> {
+    value: &'a str,
+    pos: usize,
+}
+
+impl Archive for InternedStr<'_> {
+    type Archived = InternedString;
+    type Resolver = usize;
+
+    #[inline]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        if self.value.len() <= INLINE_CAPACITY {
+            ArchivedStringRepr::emplace_inline(self.value, out.cast());
+        } else {
+            ArchivedStringRepr::emplace_out_of_line(self.value, pos, resolver, out.cast());
+        }
+    }
+}
+
+impl<S: Fallible + ?Sized> Serialize<S> for InternedStr<'_> {
+    #[inline]
+    fn serialize(&self, _: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(self.pos)
+    }
+}
+
+/// Serializes the table and indices of `field`, using `write_str` to write the bytes of each
+/// distinct string that doesn't fit inline.
+///
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` strings.
+fn serialize_interned<S: ScratchSpace + Serializer + ?Sized>(
+    field: &[String],
+    serializer: &mut S,
+    mut write_str: impl FnMut(&str, &mut S) -> Result<usize, S::Error>,
+) -> Result<InternedStringsResolver, S::Error> {
+    assert!(
+        field.len() <= u32::MAX as usize,
+        "interned strings support at most u32::MAX strings"
+    );
+
+    unsafe {
+        // first[i] is the first position of the string at position i
+        let mut first = ScratchVec::new(serializer, field.len())?;
+        for i in 0..field.len() {
+            first.push(i);
+        }
+        let unique = unique_entries(
+            field.len(),
+            |i| field[i].as_str(),
+            serializer,
+            |kept, duplicate| {
+                first[duplicate] = kept.0;
+                Ok(())
+            },
+        )?;
+
+        // slot[i] is the index in the table of the string first seen at position i
+        let mut slot = ScratchVec::new(serializer, field.len())?;
+        for _ in 0..field.len() {
+            slot.push(0u32);
+        }
+        for (index, &(i, _)) in unique.iter().enumerate() {
+            slot[i] = index as u32;
+        }
+
+        let mut positions = ScratchVec::new(serializer, unique.len())?;
+        for &(i, _) in unique.iter() {
+            let value = field[i].as_str();
+            if value.len() <= INLINE_CAPACITY {
+                positions.push(0);
+            } else {
+                positions.push(write_str(value, serializer)?);
+            }
+        }
+
+        let strings = ArchivedVec::serialize_from_iter::<InternedStr<'_>, _, _, _>(
+            unique
+                .iter()
+                .zip(positions.iter())
+                .map(|(&(i, _), &pos)| InternedStr {
+                    value: &field[i],
+                    pos,
+                }),
+            serializer,
+        )?;
+        let indices = ArchivedVec::serialize_from_iter::<u32, _, _, _>(
+            first.iter().map(|&i| slot[i]),
+            serializer,
+        )?;
+        let resolver = InternedStringsResolver {
+            strings_len: unique.len(),
+            strings,
+            indices,
+        };
+
+        positions.free(serializer)?;
+        slot.free(serializer)?;
+        unique.free(serializer)?;
+        first.free(serializer)?;
+
+        Ok(resolver)
+    }
+}
+
+impl ArchiveWith<Vec<String>> for AsInternedStrings {
+    type Archived = ArchivedInternedStrings;
+    type Resolver = InternedStringsResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<String>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let (fp, fo) = out_field!(out.strings);
+        ArchivedVec::resolve_from_len(resolver.strings_len, pos + fp, resolver.strings, fo);
+        let (fp, fo) = out_field!(out.indices);
+        ArchivedVec::resolve_from_len(field.len(), pos + fp, resolver.indices, fo);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` strings.
+impl<S: ScratchSpace + Serializer + Fallible + ?Sized> SerializeWith<Vec<String>, S>
+    for AsInternedStrings
+{
+    #[inline]
+    fn serialize_with(field: &Vec<String>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        serialize_interned(field, serializer, |value, serializer| {
+            value.serialize_unsized(serializer)
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<ArchivedInternedStrings, Vec<String>, D>
+    for AsInternedStrings
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedInternedStrings,
+        _: &mut D,
+    ) -> Result<Vec<String>, D::Error> {
+        Ok(field.iter().map(str::to_string).collect())
+    }
+}
+
+impl ArchiveWith<Vec<String>> for AsSharedInternedStrings {
+    type Archived = ArchivedInternedStrings;
+    type Resolver = InternedStringsResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<String>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        AsInternedStrings::resolve_with(field, pos, resolver, out);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` strings.
+impl<S: ScratchSpace + Serializer + StringRegistry + ?Sized> SerializeWith<Vec<String>, S>
+    for AsSharedInternedStrings
+{
+    #[inline]
+    fn serialize_with(field: &Vec<String>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        serialize_interned(field, serializer, |value, serializer| {
+            if let Some(pos) = serializer.get_string(value) {
+                Ok(pos)
+            } else {
+                let pos = value.serialize_unsized(serializer)?;
+                serializer.add_string(value, pos)?;
+                Ok(pos)
+            }
+        })
+    }
+}
+
+impl<D: Fallible + ?Sized> DeserializeWith<ArchivedInternedStrings, Vec<String>, D>
+    for AsSharedInternedStrings
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedInternedStrings,
+        deserializer: &mut D,
+    ) -> Result<Vec<String>, D::Error> {
+        AsInternedStrings::deserialize_with(field, deserializer)
+    }
+}
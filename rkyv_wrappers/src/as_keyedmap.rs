@@ -0,0 +1,156 @@
+//! A wrapper that converts a `Vec` of values to an `ArchivedHashMap` keyed by a field of each value
+//! at serialization time.
+
+use rkyv::{
+    collections::hash_map::{ArchivedHashMap, HashMapResolver},
+    ser::{ScratchSpace, Serializer},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, Serialize,
+};
+use std::{hash::Hash, marker::PhantomData};
+
+/// Extracts the key of a value for [`AsKeyedMap`].
+///
+/// Implement this on a unit struct for each key you want to look values up by:
+///
+/// ```rust
+/// use rkyv_wrappers::as_keyedmap::KeyFn;
+///
+/// struct User {
+///     id: u64,
+///     name: String,
+/// }
+///
+/// struct ById;
+///
+/// impl KeyFn<User> for ById {
+///     type Key = u64;
+///
+///     fn key(user: &User) -> &u64 {
+///         &user.id
+///     }
+/// }
+/// ```
+pub trait KeyFn<V> {
+    /// The type of the key.
+    type Key;
+
+    /// Returns the key of `value`.
+    fn key(value: &V) -> &Self::Key;
+}
+
+/// A wrapper that converts a vector of values to and from an `ArchivedHashMap` keyed by the
+/// [`KeyFn`] `F`.
+///
+/// This avoids duplicating a field of each value into a separate key just to use
+/// [`AsHashMap`](crate::as_hashmap::AsHashMap). The key is serialized once for the map and again as
+/// part of its value.
+///
+/// The user must guarantee that no two values have the same key.
+///
+/// Deserializing produces a vector of the values in the order of the archived map.
+///
+/// With the `validation` feature, the archived map is checked like any other `ArchivedHashMap`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_keyedmap::{AsKeyedMap, KeyFn};
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct User {
+///     id: u64,
+///     name: String,
+/// }
+///
+/// struct ById;
+///
+/// impl KeyFn<User> for ById {
+///     type Key = u64;
+///
+///     fn key(user: &User) -> &u64 {
+///         &user.id
+///     }
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct Users {
+///     #[with(AsKeyedMap<ById>)]
+///     pub users: Vec<User>,
+/// }
+/// let original = Users {
+///     users: vec![
+///         User { id: 7, name: String::from("alice") },
+///         User { id: 3, name: String::from("bob") },
+///     ],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<Users>(&buffer)
+/// };
+/// assert_eq!(output.users.get(&3).unwrap().name, "bob");
+/// assert!(output.users.get(&1).is_none());
+/// let mut deserialized: Users = output.deserialize(&mut Infallible).unwrap();
+/// deserialized.users.sort_by_key(|user| user.id);
+/// assert_eq!(deserialized.users[0].name, "bob");
+/// assert_eq!(deserialized.users[1].name, "alice");
+/// ```
+pub struct AsKeyedMap<F> {
+    _key: PhantomData<F>,
+}
+
+impl<V: Archive, F: KeyFn<V>> ArchiveWith<Vec<V>> for AsKeyedMap<F>
+where
+    F::Key: Archive,
+{
+    type Archived = ArchivedHashMap<Archived<F::Key>, V::Archived>;
+    type Resolver = HashMapResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<V>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        ArchivedHashMap::resolve_from_len(field.len(), pos, resolver, out);
+    }
+}
+
+impl<V, F, S> SerializeWith<Vec<V>, S> for AsKeyedMap<F>
+where
+    V: Archive + Serialize<S>,
+    F: KeyFn<V>,
+    F::Key: Archive + Serialize<S> + Hash + Eq,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    #[inline]
+    fn serialize_with(field: &Vec<V>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        // The user must guarantee that all the keys are unique
+        unsafe {
+            ArchivedHashMap::serialize_from_iter(
+                field.iter().map(|value| (F::key(value), value)),
+                serializer,
+            )
+        }
+    }
+}
+
+impl<V: Archive, F: KeyFn<V>, D: Fallible + ?Sized>
+    DeserializeWith<ArchivedHashMap<Archived<F::Key>, V::Archived>, Vec<V>, D> for AsKeyedMap<F>
+where
+    F::Key: Archive,
+    V::Archived: Deserialize<V, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedHashMap<Archived<F::Key>, V::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<V>, D::Error> {
+        field
+            .values()
+            .map(|value| value.deserialize(deserializer))
+            .collect()
+    }
+}
@@ -0,0 +1,227 @@
+//! A wrapper that groups a `Vec` of key-value pairs into an `ArchivedHashMap` of vectors at
+//! serialization time.
+
+use crate::as_hashmap::unique_entries;
+use rkyv::{
+    collections::hash_map::{ArchivedHashMap, HashMapResolver},
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::hash::Hash;
+
+/// A wrapper that converts a vector with repeated keys to and from an `ArchivedHashMap` whose
+/// values are archived vectors.
+///
+/// `AsHashMap` requires every key in the vector to be unique. `AsMultiMap` instead groups all of
+/// the values that share a key into a single `ArchivedVec`, keeping them in the order they appear
+/// in the source vector. Grouping is done with temporary tables allocated in the serializer's
+/// scratch space.
+///
+/// Deserializing flattens the map back into a vector of key-value pairs. Entries with the same key
+/// are adjacent in the result and keep their relative order, but the order of the keys themselves
+/// follows the archived map.
+///
+/// With the `validation` feature, the archived map is checked like any other `ArchivedHashMap`,
+/// and each of its values is checked as an `ArchivedVec`.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithMultiMap {
+///     #[with(rkyv_wrappers::as_multimap::AsMultiMap)]
+///     pub tags: Vec<(String, u32)>,
+/// }
+/// let original = StructWithMultiMap {
+///     tags: vec![
+///         (String::from("red"), 1),
+///         (String::from("blue"), 2),
+///         (String::from("red"), 3),
+///     ],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithMultiMap>(&buffer)
+/// };
+/// assert_eq!(output.tags.get("red").unwrap().as_slice(), &[1, 3]);
+/// assert_eq!(output.tags.get("blue").unwrap().as_slice(), &[2]);
+/// let deserialized: StructWithMultiMap = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.tags.len(), 3);
+/// ```
+pub struct AsMultiMap;
+
+/// The resolver for [`AsMultiMap`].
+pub struct MultiMapResolver {
+    len: usize,
+    inner: HashMapResolver,
+}
+
+/// The values that share a key, linked together through `next`.
+struct Group<'a, K, V> {
+    entries: &'a [(K, V)],
+    next: &'a [usize],
+    first: usize,
+    len: usize,
+}
+
+impl<'a, K, V> Group<'a, K, V> {
+    #[inline]
+    fn iter(&self) -> GroupIter<'a, K, V> {
+        GroupIter {
+            entries: self.entries,
+            next: self.next,
+            current: self.first,
+            remaining: self.len,
+        }
+    }
+}
+
+struct GroupIter<'a, K, V> {
+    entries: &'a [(K, V)],
+    next: &'a [usize],
+    current: usize,
+    remaining: usize,
+}
+
+impl<'a, K, V> Iterator for GroupIter<'a, K, V> {
+    type Item = &'a V;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.remaining == 0 {
+            None
+        } else {
+            let value = &self.entries[self.current].1;
+            self.current = self.next[self.current];
+            self.remaining -= 1;
+            Some(value)
+        }
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.remaining, Some(self.remaining))
+    }
+}
+
+impl<K, V> ExactSizeIterator for GroupIter<'_, K, V> {}
+
+impl<K, V: Archive> Archive for Group<'_, K, V> {
+    type Archived = ArchivedVec<V::Archived>;
+    type Resolver = VecResolver;
+
+    #[inline]
+    unsafe fn resolve(&self, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        ArchivedVec::resolve_from_len(self.len, pos, resolver, out);
+    }
+}
+
+impl<K, V: Serialize<S>, S: ScratchSpace + Serializer + ?Sized> Serialize<S> for Group<'_, K, V> {
+    #[inline]
+    fn serialize(&self, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        ArchivedVec::serialize_from_iter::<V, _, _, _>(self.iter(), serializer)
+    }
+}
+
+impl<K: Archive, V: Archive> ArchiveWith<Vec<(K, V)>> for AsMultiMap {
+    type Archived = ArchivedHashMap<K::Archived, ArchivedVec<V::Archived>>;
+    type Resolver = MultiMapResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        _: &Vec<(K, V)>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        ArchivedHashMap::resolve_from_len(resolver.len, pos, resolver.inner, out);
+    }
+}
+
+impl<
+        K: Archive + Serialize<S> + Hash + Eq,
+        V: Archive + Serialize<S>,
+        S: ScratchSpace + Serializer + Fallible + ?Sized,
+    > SerializeWith<Vec<(K, V)>, S> for AsMultiMap
+{
+    #[inline]
+    fn serialize_with(field: &Vec<(K, V)>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        unsafe {
+            // next[i] is the index of the entry after i with the same key
+            let mut next = ScratchVec::new(serializer, field.len())?;
+            for _ in 0..field.len() {
+                next.push(usize::MAX);
+            }
+
+            // Each key keeps the indices of the first and last entries in its group
+            let keys = unique_entries(
+                field.len(),
+                |i| &field[i].0,
+                serializer,
+                |(_, last), duplicate| {
+                    next[*last] = duplicate;
+                    *last = duplicate;
+                    Ok(())
+                },
+            )?;
+
+            let mut groups = ScratchVec::new(serializer, keys.len())?;
+            for &(first, last) in keys.iter() {
+                let mut len = 1;
+                let mut current = first;
+                while current != last {
+                    current = next[current];
+                    len += 1;
+                }
+                groups.push(Group {
+                    entries: field.as_slice(),
+                    next: &next,
+                    first,
+                    len,
+                });
+            }
+
+            let inner = ArchivedHashMap::serialize_from_iter(
+                keys.iter()
+                    .zip(groups.iter())
+                    .map(|(&(k, _), group)| (&field[k].0, group)),
+                serializer,
+            )?;
+            let len = keys.len();
+
+            groups.free(serializer)?;
+            keys.free(serializer)?;
+            next.free(serializer)?;
+
+            Ok(MultiMapResolver { len, inner })
+        }
+    }
+}
+
+impl<K: Archive, V: Archive, D: Fallible + ?Sized>
+    DeserializeWith<ArchivedHashMap<K::Archived, ArchivedVec<V::Archived>>, Vec<(K, V)>, D>
+    for AsMultiMap
+where
+    K::Archived: Deserialize<K, D>,
+    V::Archived: Deserialize<V, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedHashMap<K::Archived, ArchivedVec<V::Archived>>,
+        deserializer: &mut D,
+    ) -> Result<Vec<(K, V)>, D::Error> {
+        let mut result = Vec::with_capacity(field.values().map(|values| values.len()).sum());
+        for (key, values) in field.iter() {
+            for value in values.iter() {
+                result.push((
+                    key.deserialize(deserializer)?,
+                    value.deserialize(deserializer)?,
+                ));
+            }
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,392 @@
+//! A wrapper that quantizes a `Vec` of floats to bytes at serialization time.
+
+use crate::archived::native;
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible,
+};
+use std::{error::Error, fmt, iter::FusedIterator, marker::PhantomData};
+
+/// A wrapper that quantizes a vector of floats into an [`ArchivedQuantizedU8`].
+///
+/// `AsQuantizedU8` maps the range between the smallest and largest float of the vector onto the
+/// 256 values of a byte, and archives one byte per float along with the offset and scale of the
+/// mapping. The float with quantized value `q` decodes to `offset + q * scale`, which is within
+/// `scale / 2` of the original float. Vectors whose floats are all equal decode exactly.
+///
+/// `AsQuantizedU8` works with `Vec<f32>` and `Vec<f64>`. Infinite and NaN floats cannot be
+/// quantized, and serializing them fails with a [`NonFiniteError`], so the serializer's error type
+/// must implement `From<NonFiniteError>`. See [Serializer errors](crate#serializer-errors).
+///
+/// With the `validation` feature, checking an `ArchivedQuantizedU8` verifies that the offset and
+/// scale are finite and that the scale is not negative.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+/// use rkyv_wrappers::{
+///     as_quantizedu8::{AsQuantizedU8, NonFiniteError},
+///     BoxedErrorSerializer,
+/// };
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct StructWithTelemetry {
+///     #[with(AsQuantizedU8)]
+///     pub temperatures: Vec<f32>,
+/// }
+/// let original = StructWithTelemetry {
+///     temperatures: vec![-10.0, 15.5, 40.0, 21.25],
+/// };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// serializer.serialize_value(&original).unwrap();
+/// let buffer = serializer.into_inner();
+/// let output = unsafe {
+///     archived_root::<StructWithTelemetry>(&buffer)
+/// };
+/// assert_eq!(output.temperatures.len(), 4);
+/// assert_eq!(output.temperatures.get(0), Some(-10.0));
+/// assert_eq!(output.temperatures.get(2), Some(40.0));
+/// let scale = output.temperatures.scale() as f32;
+/// assert!((output.temperatures.get(1).unwrap() - 15.5).abs() <= scale / 2.0);
+/// let deserialized: StructWithTelemetry = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.temperatures.len(), 4);
+///
+/// let broken = StructWithTelemetry {
+///     temperatures: vec![1.0, f32::NAN],
+/// };
+/// let mut serializer = BoxedErrorSerializer::default();
+/// let error = serializer.serialize_value(&broken).unwrap_err();
+/// assert_eq!(error.downcast_ref::<NonFiniteError>().unwrap().index, 1);
+/// ```
+pub struct AsQuantizedU8;
+
+/// An error returned by [`AsQuantizedU8`] when a vector contains an infinite or NaN float.
+#[derive(Debug)]
+pub struct NonFiniteError {
+    /// The index of the first float that is not finite
+    pub index: usize,
+}
+
+impl fmt::Display for NonFiniteError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "non-finite float: float {} cannot be quantized",
+            self.index
+        )
+    }
+}
+
+impl Error for NonFiniteError {}
+
+/// A float type that can be quantized by [`AsQuantizedU8`].
+pub trait Quantize: Copy {
+    /// Converts the float to an `f64`.
+    fn to_f64(self) -> f64;
+
+    /// Converts an `f64` to the nearest float of this type.
+    fn from_f64(value: f64) -> Self;
+}
+
+impl Quantize for f32 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        f64::from(self)
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value as f32
+    }
+}
+
+impl Quantize for f64 {
+    #[inline]
+    fn to_f64(self) -> f64 {
+        self
+    }
+
+    #[inline]
+    fn from_f64(value: f64) -> Self {
+        value
+    }
+}
+
+/// An archived vector of floats quantized to bytes.
+///
+/// This is the archived version of vectors labeled [`AsQuantizedU8`]. Floats are decoded when they
+/// are accessed.
+#[repr(C)]
+pub struct ArchivedQuantizedU8<T> {
+    offset: Archived<f64>,
+    scale: Archived<f64>,
+    data: ArchivedVec<u8>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: Quantize> ArchivedQuantizedU8<T> {
+    /// Gets the number of floats in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Returns whether the vector contains no floats.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Gets the float that a quantized value of zero decodes to, which is the smallest float of
+    /// the original vector.
+    #[inline]
+    pub fn offset(&self) -> f64 {
+        native::<f64>(self.offset)
+    }
+
+    /// Gets the difference between the floats that consecutive quantized values decode to.
+    #[inline]
+    pub fn scale(&self) -> f64 {
+        native::<f64>(self.scale)
+    }
+
+    /// Decodes the float at the given position.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.data
+            .get(index)
+            .map(|&quantized| self.decode(quantized))
+    }
+
+    /// Gets the quantized values of the floats.
+    #[inline]
+    pub fn quantized(&self) -> &[u8] {
+        self.data.as_slice()
+    }
+
+    /// Gets an iterator over the decoded floats of the vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            vec: self,
+            quantized: self.data.iter(),
+        }
+    }
+
+    #[inline]
+    fn decode(&self, quantized: u8) -> T {
+        // A fused multiply-add can't overflow when the range of the floats is wider than `f64::MAX`
+        T::from_f64(f64::from(quantized).mul_add(self.scale(), self.offset()))
+    }
+}
+
+impl<T: Quantize + fmt::Debug> fmt::Debug for ArchivedQuantizedU8<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: Quantize> IntoIterator for &'a ArchivedQuantizedU8<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the decoded floats of an [`ArchivedQuantizedU8`].
+pub struct Iter<'a, T> {
+    vec: &'a ArchivedQuantizedU8<T>,
+    quantized: std::slice::Iter<'a, u8>,
+}
+
+impl<T: Quantize> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.quantized
+            .next()
+            .map(|&quantized| self.vec.decode(quantized))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.quantized.size_hint()
+    }
+}
+
+impl<T: Quantize> DoubleEndedIterator for Iter<'_, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.quantized
+            .next_back()
+            .map(|&quantized| self.vec.decode(quantized))
+    }
+}
+
+impl<T: Quantize> ExactSizeIterator for Iter<'_, T> {}
+impl<T: Quantize> FusedIterator for Iter<'_, T> {}
+
+/// Errors that can occur while checking an [`ArchivedQuantizedU8`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum QuantizedU8Error<D> {
+    /// An error occurred while checking the quantized values
+    DataError(D),
+    /// The offset or scale is not finite, or the scale is negative
+    InvalidMapping {
+        /// The offset of the mapping
+        offset: f64,
+        /// The scale of the mapping
+        scale: f64,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<D: fmt::Display> fmt::Display for QuantizedU8Error<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            QuantizedU8Error::DataError(e) => write!(f, "data check error: {}", e),
+            QuantizedU8Error::InvalidMapping { offset, scale } => write!(
+                f,
+                "invalid mapping: offset {} and scale {} do not map to finite floats",
+                offset, scale
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<D: std::error::Error + 'static> std::error::Error for QuantizedU8Error<D> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            QuantizedU8Error::DataError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<T, C> CheckBytes<C> for ArchivedQuantizedU8<T>
+    where
+        T: Quantize,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = QuantizedU8Error<<ArchivedVec<u8> as CheckBytes<C>>::Error>;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            ArchivedVec::<u8>::check_bytes(ptr::addr_of!((*value).data), context)
+                .map_err(QuantizedU8Error::DataError)?;
+            let value = &*value;
+
+            let (offset, scale) = (value.offset(), value.scale());
+            if !offset.is_finite() || !scale.is_finite() || scale < 0.0 {
+                return Err(QuantizedU8Error::InvalidMapping { offset, scale });
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsQuantizedU8`].
+pub struct QuantizedU8Resolver {
+    offset: f64,
+    scale: f64,
+    data: VecResolver,
+}
+
+impl<T: Quantize> ArchiveWith<Vec<T>> for AsQuantizedU8 {
+    type Archived = ArchivedQuantizedU8<T>;
+    type Resolver = QuantizedU8Resolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let (fp, fo) = out_field!(out.offset);
+        resolver.offset.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.scale);
+        resolver.scale.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.data);
+        ArchivedVec::resolve_from_len(field.len(), pos + fp, resolver.data, fo);
+    }
+}
+
+impl<T, S> SerializeWith<Vec<T>, S> for AsQuantizedU8
+where
+    T: Quantize,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    S::Error: From<NonFiniteError>,
+{
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let mut min = f64::INFINITY;
+        let mut max = f64::NEG_INFINITY;
+        for (index, value) in field.iter().enumerate() {
+            let value = value.to_f64();
+            if !value.is_finite() {
+                return Err(NonFiniteError { index }.into());
+            }
+            min = min.min(value);
+            max = max.max(value);
+        }
+
+        let (offset, mut scale) = if field.is_empty() {
+            (0.0, 0.0)
+        } else {
+            // Divide first so that the range of the floats cannot overflow
+            (min, max / 255.0 - min / 255.0)
+        };
+        // Rounding can still push the largest float past `f64::MAX` when the range is that wide
+        while 255f64.mul_add(scale, offset).is_infinite() {
+            scale = f64::from_bits(scale.to_bits() - 1);
+        }
+        let data = ArchivedVec::serialize_from_iter::<u8, _, _, _>(
+            field.iter().map(|value| {
+                if scale == 0.0 {
+                    0
+                } else {
+                    ((value.to_f64() - offset) / scale)
+                        .round()
+                        .clamp(0.0, 255.0) as u8
+                }
+            }),
+            serializer,
+        )?;
+
+        Ok(QuantizedU8Resolver {
+            offset,
+            scale,
+            data,
+        })
+    }
+}
+
+impl<T, D> DeserializeWith<ArchivedQuantizedU8<T>, Vec<T>, D> for AsQuantizedU8
+where
+    T: Quantize,
+    D: Fallible + ?Sized,
+{
+    #[inline]
+    fn deserialize_with(field: &ArchivedQuantizedU8<T>, _: &mut D) -> Result<Vec<T>, D::Error> {
+        Ok(field.iter().collect())
+    }
+}
@@ -0,0 +1,371 @@
+//! A wrapper that run-length encodes a `Vec` at serialization time.
+
+use crate::archived::to_usize;
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::{fmt, iter::FusedIterator};
+
+/// A wrapper that run-length encodes a vector into an [`ArchivedRunLength`].
+///
+/// Sensor readings, image masks, and other data with long runs of identical values archive the
+/// same value over and over. `AsRunLength` archives each run of equal values once along with the
+/// cumulative length of the runs up to and including it. Accessing an element by index binary
+/// searches the cumulative lengths, so it takes `O(log n)` time in the number of runs.
+///
+/// Deserializing expands the runs back into the original vector by cloning the value of each run.
+///
+/// With the `validation` feature, checking an `ArchivedRunLength` checks every value and verifies
+/// that no run is empty.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_runlength::AsRunLength;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithMask {
+///     #[with(AsRunLength)]
+///     pub mask: Vec<u8>,
+/// }
+/// let original = StructWithMask {
+///     mask: [vec![0; 1000], vec![255; 24], vec![0; 1000]].concat(),
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithMask>(&buffer)
+/// };
+/// assert_eq!(output.mask.len(), 2024);
+/// assert_eq!(output.mask.run_count(), 3);
+/// assert_eq!(output.mask.get(1010), Some(&255));
+/// assert!(output.mask.runs().eq([(&0, 1000), (&255, 24), (&0, 1000)].iter().copied()));
+/// let deserialized: StructWithMask = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsRunLength;
+
+/// An archived run-length encoded vector.
+///
+/// This is the archived version of vectors labeled [`AsRunLength`].
+#[repr(C)]
+pub struct ArchivedRunLength<T> {
+    values: ArchivedVec<T>,
+    ends: ArchivedVec<Archived<u32>>,
+}
+
+impl<T> ArchivedRunLength<T> {
+    /// Gets the number of elements in the expanded vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.ends.last().map_or(0, |&end| to_usize(end))
+    }
+
+    /// Returns whether the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.ends.is_empty()
+    }
+
+    /// Gets the number of runs of equal elements.
+    #[inline]
+    pub fn run_count(&self) -> usize {
+        self.ends.len()
+    }
+
+    /// Gets the element at the given position of the expanded vector.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<&T> {
+        let run = self.ends.partition_point(|&end| to_usize(end) <= index);
+        self.values.get(run)
+    }
+
+    /// Gets the value of each run.
+    #[inline]
+    pub fn values(&self) -> &[T] {
+        self.values.as_slice()
+    }
+
+    /// Gets an iterator over the value and length of each run.
+    #[inline]
+    pub fn runs(&self) -> Runs<'_, T> {
+        Runs {
+            values: self.values.iter(),
+            ends: self.ends.iter(),
+            start: 0,
+        }
+    }
+
+    /// Gets an iterator over the elements of the expanded vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            runs: self.runs(),
+            current: None,
+            remaining: 0,
+            len: self.len(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArchivedRunLength<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a ArchivedRunLength<T> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the runs of an [`ArchivedRunLength`].
+pub struct Runs<'a, T> {
+    values: std::slice::Iter<'a, T>,
+    ends: std::slice::Iter<'a, Archived<u32>>,
+    start: usize,
+}
+
+impl<'a, T> Iterator for Runs<'a, T> {
+    type Item = (&'a T, usize);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let value = self.values.next()?;
+        let end = to_usize(*self.ends.next()?);
+        let run = end - self.start;
+        self.start = end;
+        Some((value, run))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for Runs<'_, T> {}
+impl<T> FusedIterator for Runs<'_, T> {}
+
+/// An iterator over the elements of an [`ArchivedRunLength`].
+pub struct Iter<'a, T> {
+    runs: Runs<'a, T>,
+    current: Option<&'a T>,
+    remaining: usize,
+    len: usize,
+}
+
+impl<'a, T> Iterator for Iter<'a, T> {
+    type Item = &'a T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.remaining == 0 {
+            let (value, run) = self.runs.next()?;
+            self.current = Some(value);
+            self.remaining = run;
+        }
+        self.remaining -= 1;
+        self.len -= 1;
+        self.current
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (self.len, Some(self.len))
+    }
+}
+
+impl<T> ExactSizeIterator for Iter<'_, T> {}
+impl<T> FusedIterator for Iter<'_, T> {}
+
+/// Errors that can occur while checking an [`ArchivedRunLength`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum RunLengthError<V, E> {
+    /// An error occurred while checking the values
+    ValuesError(V),
+    /// An error occurred while checking the cumulative lengths
+    EndsError(E),
+    /// The number of values does not match the number of cumulative lengths
+    LengthMismatch,
+    /// A run is empty
+    EmptyRun {
+        /// The position of the run
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<V: fmt::Display, E: fmt::Display> fmt::Display for RunLengthError<V, E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RunLengthError::ValuesError(e) => write!(f, "values check error: {}", e),
+            RunLengthError::EndsError(e) => write!(f, "ends check error: {}", e),
+            RunLengthError::LengthMismatch => {
+                write!(f, "length mismatch: values and ends have different lengths")
+            }
+            RunLengthError::EmptyRun { index } => write!(f, "empty run: run {}", index),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<V, E> std::error::Error for RunLengthError<V, E>
+where
+    V: std::error::Error + 'static,
+    E: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            RunLengthError::ValuesError(e) => Some(e as &dyn std::error::Error),
+            RunLengthError::EndsError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<T, C> CheckBytes<C> for ArchivedRunLength<T>
+    where
+        T: CheckBytes<C>,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = RunLengthError<
+            <ArchivedVec<T> as CheckBytes<C>>::Error,
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            // Subtrees have to be checked in the order they were serialized
+            let values = ArchivedVec::check_bytes(ptr::addr_of!((*value).values), context)
+                .map_err(RunLengthError::ValuesError)?;
+            let ends =
+                ArchivedVec::<Archived<u32>>::check_bytes(ptr::addr_of!((*value).ends), context)
+                    .map_err(RunLengthError::EndsError)?;
+
+            if values.len() != ends.len() {
+                return Err(RunLengthError::LengthMismatch);
+            }
+            let mut start = 0;
+            for (index, &end) in ends.iter().enumerate() {
+                let end = to_usize(end);
+                if end <= start {
+                    return Err(RunLengthError::EmptyRun { index });
+                }
+                start = end;
+            }
+
+            Ok(&*value)
+        }
+    }
+};
+
+/// The resolver for [`AsRunLength`].
+pub struct RunLengthResolver {
+    runs: usize,
+    values: VecResolver,
+    ends: VecResolver,
+}
+
+impl<T: Archive> ArchiveWith<Vec<T>> for AsRunLength {
+    type Archived = ArchivedRunLength<T::Archived>;
+    type Resolver = RunLengthResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        _: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let (fp, fo) = out_field!(out.values);
+        ArchivedVec::resolve_from_len(resolver.runs, pos + fp, resolver.values, fo);
+        let (fp, fo) = out_field!(out.ends);
+        ArchivedVec::resolve_from_len(resolver.runs, pos + fp, resolver.ends, fo);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` elements.
+impl<T, S> SerializeWith<Vec<T>, S> for AsRunLength
+where
+    T: Serialize<S> + PartialEq,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        assert!(
+            field.len() <= u32::MAX as usize,
+            "AsRunLength supports at most u32::MAX elements"
+        );
+
+        unsafe {
+            let runs = (1..field.len())
+                .filter(|&i| field[i] != field[i - 1])
+                .count()
+                + usize::from(!field.is_empty());
+            let mut ends = ScratchVec::new(serializer, runs)?;
+            for i in 1..field.len() {
+                if field[i] != field[i - 1] {
+                    ends.push(i as u32);
+                }
+            }
+            if !field.is_empty() {
+                ends.push(field.len() as u32);
+            }
+
+            let values = ArchivedVec::serialize_from_iter::<T, _, _, _>(
+                (0..runs).map(|run| {
+                    let start = if run == 0 { 0 } else { ends[run - 1] as usize };
+                    &field[start]
+                }),
+                serializer,
+            )?;
+            let resolver = RunLengthResolver {
+                runs,
+                values,
+                ends: ArchivedVec::serialize_from_slice(&ends, serializer)?,
+            };
+
+            ends.free(serializer)?;
+
+            Ok(resolver)
+        }
+    }
+}
+
+impl<T, D> DeserializeWith<ArchivedRunLength<T::Archived>, Vec<T>, D> for AsRunLength
+where
+    T: Archive + Clone,
+    T::Archived: Deserialize<T, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize_with(
+        field: &ArchivedRunLength<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<T>, D::Error> {
+        let mut result = Vec::with_capacity(field.len());
+        for (value, run) in field.runs() {
+            let value = value.deserialize(deserializer)?;
+            result.resize(result.len() + run, value);
+        }
+        Ok(result)
+    }
+}
@@ -0,0 +1,409 @@
+//! A wrapper that converts a `Vec` to a sorted archived vector at serialization time.
+
+use crate::{
+    as_hashmap::{sorted_entries, DuplicateKeyPolicy, Entries, FromEntries, LastWins},
+    as_indexmap::{Entry, EntryRef},
+};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, Serialize,
+};
+use std::{
+    borrow::Borrow,
+    fmt,
+    iter::FusedIterator,
+    marker::PhantomData,
+    ops::{Bound, RangeBounds},
+    slice,
+};
+
+/// A wrapper that converts a vector to and from an [`ArchivedSortedVec`], which looks up entries
+/// by binary search.
+///
+/// For small maps that are mostly read, a hash table is often slower than searching a sorted
+/// array, and its layout is less cache friendly. `AsSortedVec` sorts the indices of the entries in
+/// the serializer's scratch space and writes the entries to a single `ArchivedVec` in key order.
+/// If the vector is already sorted by key with no repeated keys, sorting is skipped entirely.
+///
+/// Entries that repeat a key are handled by the [`DuplicateKeyPolicy`] `P`. The default,
+/// [`LastWins`], matches collecting the vector into a `BTreeMap`.
+///
+/// Any container that implements [`Entries`] can be labeled `AsSortedVec`, and any collection that
+/// implements [`FromEntries`] can be deserialized from it. Deserializing produces one entry per
+/// unique key, sorted by key.
+///
+/// With the `validation` feature, checking an `ArchivedSortedVec` checks its entries and then
+/// verifies that their keys are strictly increasing, so lookups on a checked archive always find
+/// the right entry.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithSortedVec {
+///     #[with(rkyv_wrappers::as_sortedvec::AsSortedVec)]
+///     pub sorted: Vec<(u32, String)>,
+/// }
+/// let original = StructWithSortedVec {
+///     sorted: vec![(3, String::from("c")), (1, String::from("a")), (2, String::from("b"))]
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithSortedVec>(&buffer)
+/// };
+/// assert_eq!(output.sorted.get(&1).unwrap(), &"a");
+/// assert!(!output.sorted.contains_key(&4));
+/// assert_eq!(output.sorted.range(2..).map(|(k, _)| *k).collect::<Vec<_>>(), vec![2, 3]);
+/// let deserialized: StructWithSortedVec = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.sorted[0], (1, String::from("a")));
+/// ```
+pub struct AsSortedVec<P = LastWins> {
+    _policy: PhantomData<P>,
+}
+
+/// An archived map stored as a vector of entries sorted by key.
+///
+/// This is the archived version of vectors labeled [`AsSortedVec`].
+#[repr(transparent)]
+pub struct ArchivedSortedVec<K, V> {
+    entries: ArchivedVec<Entry<K, V>>,
+}
+
+impl<K, V> ArchivedSortedVec<K, V> {
+    /// Gets the number of entries in the map.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the map contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// Returns the position of the first entry with a key that is not less than the given key.
+    ///
+    /// If every key is less than the given key, this returns the length of the map.
+    #[inline]
+    pub fn lower_bound<Q>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries
+            .as_slice()
+            .partition_point(|entry| entry.key.borrow() < k)
+    }
+
+    /// Returns the position of the first entry with a key that is greater than the given key.
+    ///
+    /// If no key is greater than the given key, this returns the length of the map.
+    #[inline]
+    pub fn upper_bound<Q>(&self, k: &Q) -> usize
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.entries
+            .as_slice()
+            .partition_point(|entry| entry.key.borrow() <= k)
+    }
+
+    /// Gets the key and value of the entry with the given key.
+    #[inline]
+    pub fn get_key_value<Q>(&self, k: &Q) -> Option<(&K, &V)>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        let index = self
+            .entries
+            .as_slice()
+            .binary_search_by(|entry| entry.key.borrow().cmp(k))
+            .ok()?;
+        let entry = &self.entries[index];
+        Some((&entry.key, &entry.value))
+    }
+
+    /// Gets the value associated with the given key.
+    #[inline]
+    pub fn get<Q>(&self, k: &Q) -> Option<&V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(k).map(|(_, v)| v)
+    }
+
+    /// Returns whether a key is present in the map.
+    #[inline]
+    pub fn contains_key<Q>(&self, k: &Q) -> bool
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+    {
+        self.get_key_value(k).is_some()
+    }
+
+    /// Gets an iterator over the entries with keys in the given range, in key order.
+    ///
+    /// Unlike `BTreeMap::range`, an empty or decreasing range returns an empty iterator instead of
+    /// panicking.
+    #[inline]
+    pub fn range<Q, R>(&self, range: R) -> Iter<'_, K, V>
+    where
+        K: Borrow<Q>,
+        Q: Ord + ?Sized,
+        R: RangeBounds<Q>,
+    {
+        let start = match range.start_bound() {
+            Bound::Included(k) => self.lower_bound(k),
+            Bound::Excluded(k) => self.upper_bound(k),
+            Bound::Unbounded => 0,
+        };
+        let end = match range.end_bound() {
+            Bound::Included(k) => self.upper_bound(k),
+            Bound::Excluded(k) => self.lower_bound(k),
+            Bound::Unbounded => self.len(),
+        };
+        Iter {
+            inner: self.entries[start..end.max(start)].iter(),
+        }
+    }
+
+    /// Gets the key and value of the entry at the given position.
+    #[inline]
+    pub fn get_index(&self, index: usize) -> Option<(&K, &V)> {
+        self.entries
+            .get(index)
+            .map(|entry| (&entry.key, &entry.value))
+    }
+
+    /// Gets the entries of the map in key order.
+    #[inline]
+    pub fn as_slice(&self) -> &[Entry<K, V>] {
+        self.entries.as_slice()
+    }
+
+    /// Gets an iterator over the entries of the map in key order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, K, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// Gets an iterator over the keys of the map in order.
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &K> + ExactSizeIterator {
+        self.entries.iter().map(|entry| &entry.key)
+    }
+
+    /// Gets an iterator over the values of the map in key order.
+    #[inline]
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|entry| &entry.value)
+    }
+}
+
+impl<K: fmt::Debug, V: fmt::Debug> fmt::Debug for ArchivedSortedVec<K, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, K, V> IntoIterator for &'a ArchivedSortedVec<K, V> {
+    type Item = (&'a K, &'a V);
+    type IntoIter = Iter<'a, K, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an [`ArchivedSortedVec`] in key order.
+pub struct Iter<'a, K, V> {
+    inner: slice::Iter<'a, Entry<K, V>>,
+}
+
+impl<'a, K, V> Iterator for Iter<'a, K, V> {
+    type Item = (&'a K, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner.next().map(|entry| (&entry.key, &entry.value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<K, V> DoubleEndedIterator for Iter<'_, K, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| (&entry.key, &entry.value))
+    }
+}
+
+impl<K, V> ExactSizeIterator for Iter<'_, K, V> {}
+impl<K, V> FusedIterator for Iter<'_, K, V> {}
+
+/// Errors that can occur while checking an [`ArchivedSortedVec`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum SortedVecError<E> {
+    /// An error occurred while checking the entries
+    EntriesError(E),
+    /// The key of an entry is not greater than the key of the entry before it
+    Unsorted {
+        /// The position of the entry
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<E: fmt::Display> fmt::Display for SortedVecError<E> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SortedVecError::EntriesError(e) => write!(f, "entries check error: {}", e),
+            SortedVecError::Unsorted { index } => write!(
+                f,
+                "unsorted entries: entry {} is not greater than the entry before it",
+                index
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<E: std::error::Error + 'static> std::error::Error for SortedVecError<E> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SortedVecError::EntriesError(e) => Some(e as &dyn std::error::Error),
+            SortedVecError::Unsorted { .. } => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<K, V, C> CheckBytes<C> for ArchivedSortedVec<K, V>
+    where
+        K: CheckBytes<C> + Ord,
+        V: CheckBytes<C>,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = SortedVecError<<ArchivedVec<Entry<K, V>> as CheckBytes<C>>::Error>;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let entries = ArchivedVec::check_bytes(ptr::addr_of!((*value).entries), context)
+                .map_err(SortedVecError::EntriesError)?;
+            for (i, pair) in entries.windows(2).enumerate() {
+                if pair[0].key >= pair[1].key {
+                    return Err(SortedVecError::Unsorted { index: i + 1 });
+                }
+            }
+            Ok(&*value)
+        }
+    }
+};
+
+/// The resolver for [`AsSortedVec`].
+pub struct SortedVecResolver {
+    len: usize,
+    inner: VecResolver,
+}
+
+impl<F: Entries, P> ArchiveWith<F> for AsSortedVec<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+{
+    type Archived = ArchivedSortedVec<Archived<F::Key>, Archived<F::Value>>;
+    type Resolver = SortedVecResolver;
+
+    #[inline]
+    unsafe fn resolve_with(_: &F, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.entries);
+        ArchivedVec::resolve_from_len(resolver.len, pos + fp, resolver.inner, fo);
+    }
+}
+
+impl<F, S, P> SerializeWith<F, S> for AsSortedVec<P>
+where
+    F: Entries,
+    F::Key: Serialize<S> + Ord,
+    F::Value: Serialize<S>,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    P: DuplicateKeyPolicy<S::Error>,
+{
+    #[inline]
+    fn serialize_with(field: &F, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let len = field.len();
+        if (1..len).all(|i| field.entry(i - 1).0 < field.entry(i).0) {
+            // Already sorted with unique keys
+            let inner = ArchivedVec::serialize_from_iter::<EntryRef<'_, F::Key, F::Value>, _, _, _>(
+                (0..len).map(|i| {
+                    let (key, value) = field.entry(i);
+                    EntryRef { key, value }
+                }),
+                serializer,
+            )?;
+            return Ok(SortedVecResolver { len, inner });
+        }
+
+        unsafe {
+            let entries = sorted_entries(len, |i| field.entry(i).0, serializer, P::on_duplicate)?;
+            let inner = ArchivedVec::serialize_from_iter::<EntryRef<'_, F::Key, F::Value>, _, _, _>(
+                entries.iter().map(|&(k, v)| EntryRef {
+                    key: field.entry(k).0,
+                    value: field.entry(v).1,
+                }),
+                serializer,
+            )?;
+            let len = entries.len();
+            entries.free(serializer)?;
+
+            Ok(SortedVecResolver { len, inner })
+        }
+    }
+}
+
+impl<F: FromEntries<D>, D: Fallible + ?Sized, P>
+    DeserializeWith<ArchivedSortedVec<Archived<F::Key>, Archived<F::Value>>, F, D>
+    for AsSortedVec<P>
+where
+    F::Key: Archive,
+    F::Value: Archive,
+    Archived<F::Key>: Deserialize<F::Key, D>,
+    Archived<F::Value>: Deserialize<F::Value, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedSortedVec<Archived<F::Key>, Archived<F::Value>>,
+        deserializer: &mut D,
+    ) -> Result<F, D::Error> {
+        let entries = field
+            .iter()
+            .map(|(k, v)| Ok((k.deserialize(deserializer)?, v.deserialize(deserializer)?)))
+            .collect::<Result<Vec<_>, _>>()?;
+        F::from_entries(entries)
+    }
+}
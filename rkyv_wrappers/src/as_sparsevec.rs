@@ -0,0 +1,367 @@
+//! A wrapper that archives only the non-default elements of a `Vec` at serialization time.
+
+use crate::archived::to_usize;
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::{fmt, iter::FusedIterator, slice};
+
+/// A wrapper that converts a vector to and from an [`ArchivedSparseVec`], which only stores the
+/// elements that are not equal to `T::default()`.
+///
+/// Feature vectors, embeddings, and other mostly-zero data spend most of their archive on default
+/// values. `AsSparseVec` archives the length of the vector along with the sorted indices and values
+/// of its non-default elements. Accessing an element binary searches the indices and returns the
+/// default value if the element was not stored.
+///
+/// Elements are compared against `T::default()` with `PartialEq`, so `-0.0` is not stored and
+/// deserializes as `0.0`.
+///
+/// With the `validation` feature, checking an `ArchivedSparseVec` checks every stored value and
+/// verifies that the indices are strictly increasing and in bounds.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_sparsevec::AsSparseVec;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct StructWithFeatures {
+///     #[with(AsSparseVec)]
+///     pub features: Vec<f32>,
+/// }
+/// let mut features = vec![0.0; 1000];
+/// features[3] = 0.5;
+/// features[700] = -2.0;
+/// let original = StructWithFeatures { features };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithFeatures>(&buffer)
+/// };
+/// assert_eq!(output.features.len(), 1000);
+/// assert_eq!(output.features.stored_len(), 2);
+/// assert_eq!(output.features.get(3), Some(0.5));
+/// assert_eq!(output.features.get(4), Some(0.0));
+/// assert_eq!(output.features.get(1000), None);
+/// assert!(output.features.iter_sparse().eq([(3, &0.5), (700, &-2.0)].iter().copied()));
+/// let deserialized: StructWithFeatures = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsSparseVec;
+
+/// An archived sparse vector.
+///
+/// This is the archived version of vectors labeled [`AsSparseVec`].
+#[repr(C)]
+pub struct ArchivedSparseVec<T> {
+    len: Archived<u32>,
+    indices: ArchivedVec<Archived<u32>>,
+    values: ArchivedVec<T>,
+}
+
+impl<T> ArchivedSparseVec<T> {
+    /// Gets the number of elements in the vector, including default elements.
+    #[inline]
+    pub fn len(&self) -> usize {
+        to_usize(self.len)
+    }
+
+    /// Returns whether the vector contains no elements.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of non-default elements stored in the vector.
+    #[inline]
+    pub fn stored_len(&self) -> usize {
+        self.values.len()
+    }
+
+    /// Gets the element at the given position.
+    ///
+    /// Returns the default value if the element was not stored, and `None` if the position is out
+    /// of bounds.
+    #[inline]
+    pub fn get(&self, index: usize) -> Option<T>
+    where
+        T: Clone + Default,
+    {
+        if index < self.len() {
+            Some(self.get_stored(index).cloned().unwrap_or_default())
+        } else {
+            None
+        }
+    }
+
+    /// Gets the element at the given position if it was stored.
+    ///
+    /// Returns `None` if the element was not stored or the position is out of bounds.
+    #[inline]
+    pub fn get_stored(&self, index: usize) -> Option<&T> {
+        let i = self
+            .indices
+            .binary_search_by(|&stored| to_usize(stored).cmp(&index))
+            .ok()?;
+        Some(&self.values[i])
+    }
+
+    /// Gets the sorted indices of the stored elements.
+    #[inline]
+    pub fn indices(&self) -> &[Archived<u32>] {
+        self.indices.as_slice()
+    }
+
+    /// Gets the values of the stored elements.
+    #[inline]
+    pub fn values(&self) -> &[T] {
+        self.values.as_slice()
+    }
+
+    /// Gets an iterator over the index and value of each stored element.
+    #[inline]
+    pub fn iter_sparse(&self) -> SparseIter<'_, T> {
+        SparseIter {
+            indices: self.indices.iter(),
+            values: self.values.iter(),
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for ArchivedSparseVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ArchivedSparseVec")
+            .field("len", &self.len())
+            .field("elements", &DebugSparse(self))
+            .finish()
+    }
+}
+
+struct DebugSparse<'a, T>(&'a ArchivedSparseVec<T>);
+
+impl<T: fmt::Debug> fmt::Debug for DebugSparse<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.0.iter_sparse()).finish()
+    }
+}
+
+/// An iterator over the stored elements of an [`ArchivedSparseVec`].
+pub struct SparseIter<'a, T> {
+    indices: slice::Iter<'a, Archived<u32>>,
+    values: slice::Iter<'a, T>,
+}
+
+impl<'a, T> Iterator for SparseIter<'a, T> {
+    type Item = (usize, &'a T);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        let index = to_usize(*self.indices.next()?);
+        Some((index, self.values.next()?))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.values.size_hint()
+    }
+}
+
+impl<T> DoubleEndedIterator for SparseIter<'_, T> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let index = to_usize(*self.indices.next_back()?);
+        Some((index, self.values.next_back()?))
+    }
+}
+
+impl<T> ExactSizeIterator for SparseIter<'_, T> {}
+impl<T> FusedIterator for SparseIter<'_, T> {}
+
+/// Errors that can occur while checking an [`ArchivedSparseVec`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum SparseVecError<I, V> {
+    /// An error occurred while checking the indices
+    IndicesError(I),
+    /// An error occurred while checking the values
+    ValuesError(V),
+    /// The number of indices does not match the number of values
+    LengthMismatch,
+    /// An index is out of bounds or not greater than the index before it
+    InvalidIndex {
+        /// The position of the index
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<I: fmt::Display, V: fmt::Display> fmt::Display for SparseVecError<I, V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SparseVecError::IndicesError(e) => write!(f, "indices check error: {}", e),
+            SparseVecError::ValuesError(e) => write!(f, "values check error: {}", e),
+            SparseVecError::LengthMismatch => {
+                write!(
+                    f,
+                    "length mismatch: indices and values have different lengths"
+                )
+            }
+            SparseVecError::InvalidIndex { index } => write!(
+                f,
+                "invalid index: index {} is out of bounds or out of order",
+                index
+            ),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<I, V> std::error::Error for SparseVecError<I, V>
+where
+    I: std::error::Error + 'static,
+    V: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SparseVecError::IndicesError(e) => Some(e as &dyn std::error::Error),
+            SparseVecError::ValuesError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<T, C> CheckBytes<C> for ArchivedSparseVec<T>
+    where
+        T: CheckBytes<C>,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = SparseVecError<
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+            <ArchivedVec<T> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let indices =
+                ArchivedVec::<Archived<u32>>::check_bytes(ptr::addr_of!((*value).indices), context)
+                    .map_err(SparseVecError::IndicesError)?;
+            let values = ArchivedVec::check_bytes(ptr::addr_of!((*value).values), context)
+                .map_err(SparseVecError::ValuesError)?;
+            let value = &*value;
+
+            if indices.len() != values.len() {
+                return Err(SparseVecError::LengthMismatch);
+            }
+            let len = value.len();
+            let mut next = 0;
+            for (index, &i) in indices.iter().enumerate() {
+                let i = to_usize(i);
+                if i < next || i >= len {
+                    return Err(SparseVecError::InvalidIndex { index });
+                }
+                next = i + 1;
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsSparseVec`].
+pub struct SparseVecResolver {
+    stored: usize,
+    indices: VecResolver,
+    values: VecResolver,
+}
+
+impl<T: Archive> ArchiveWith<Vec<T>> for AsSparseVec {
+    type Archived = ArchivedSparseVec<T::Archived>;
+    type Resolver = SparseVecResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let (fp, fo) = out_field!(out.len);
+        (field.len() as u32).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.indices);
+        ArchivedVec::resolve_from_len(resolver.stored, pos + fp, resolver.indices, fo);
+        let (fp, fo) = out_field!(out.values);
+        ArchivedVec::resolve_from_len(resolver.stored, pos + fp, resolver.values, fo);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` elements.
+impl<T, S> SerializeWith<Vec<T>, S> for AsSparseVec
+where
+    T: Serialize<S> + Default + PartialEq,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        assert!(
+            field.len() <= u32::MAX as usize,
+            "AsSparseVec supports at most u32::MAX elements"
+        );
+
+        unsafe {
+            let default = T::default();
+            let stored = field.iter().filter(|&value| *value != default).count();
+            let mut indices = ScratchVec::new(serializer, stored)?;
+            for (i, value) in field.iter().enumerate() {
+                if *value != default {
+                    indices.push(i as u32);
+                }
+            }
+
+            let resolver = SparseVecResolver {
+                stored,
+                indices: ArchivedVec::serialize_from_slice(&indices, serializer)?,
+                values: ArchivedVec::serialize_from_iter::<T, _, _, _>(
+                    indices.iter().map(|&i| &field[i as usize]),
+                    serializer,
+                )?,
+            };
+
+            indices.free(serializer)?;
+
+            Ok(resolver)
+        }
+    }
+}
+
+impl<T, D> DeserializeWith<ArchivedSparseVec<T::Archived>, Vec<T>, D> for AsSparseVec
+where
+    T: Archive + Default,
+    T::Archived: Deserialize<T, D>,
+    D: Fallible + ?Sized,
+{
+    fn deserialize_with(
+        field: &ArchivedSparseVec<T::Archived>,
+        deserializer: &mut D,
+    ) -> Result<Vec<T>, D::Error> {
+        let mut result = Vec::with_capacity(field.len());
+        for (index, value) in field.iter_sparse() {
+            result.resize_with(index, T::default);
+            result.push(value.deserialize(deserializer)?);
+        }
+        result.resize_with(field.len(), T::default);
+        Ok(result)
+    }
+}
@@ -0,0 +1,211 @@
+//! A wrapper that archives a value as its string representation.
+
+use rkyv::{
+    out_field,
+    ser::Serializer,
+    string::{ArchivedString, StringResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Fallible,
+};
+use std::{borrow::Borrow, fmt, marker::PhantomData, ops::Deref, str::FromStr};
+
+/// A wrapper that archives a value as its [`Display`](fmt::Display) string in an
+/// [`ArchivedAsString`].
+///
+/// Addresses, versions, identifiers and other types that round-trip through strings can be
+/// archived without writing a wrapper for each of them. `AsString` archives the string that the
+/// value formats to, and deserializes the value by parsing the archived string with [`FromStr`].
+/// Parse errors are returned as deserializer errors, so the deserializer's error type must
+/// implement `From<T::Err>`. The archived string can be read directly or parsed on demand with
+/// [`ArchivedAsString::parse`].
+///
+/// With the `validation` feature, checking an `ArchivedAsString` checks that the string is valid
+/// UTF-8. It does not check that the string parses.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Fallible};
+/// use rkyv_wrappers::as_string::AsString;
+/// use std::{error::Error, net::{IpAddr, Ipv4Addr, SocketAddr}};
+///
+/// // A deserializer whose error type can hold an `AddrParseError`
+/// struct MyDeserializer;
+/// impl Fallible for MyDeserializer {
+///     type Error = Box<dyn Error>;
+/// }
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+/// struct Listener {
+///     #[with(AsString)]
+///     pub addr: SocketAddr,
+/// }
+/// let original = Listener {
+///     addr: SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), 8080),
+/// };
+/// let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<Listener>(&buffer)
+/// };
+/// assert_eq!(output.addr, "127.0.0.1:8080");
+/// assert_eq!(output.addr.parse().unwrap().port(), 8080);
+/// let deserialized: Listener = output.deserialize(&mut MyDeserializer).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+pub struct AsString;
+
+/// An archived string representation of a `T`.
+///
+/// This is the archived version of values labeled [`AsString`]. It dereferences to the archived
+/// string, and [`parse`](ArchivedAsString::parse) parses the `T` back out of it.
+#[repr(transparent)]
+pub struct ArchivedAsString<T> {
+    string: ArchivedString,
+    _phantom: PhantomData<T>,
+}
+
+impl<T> ArchivedAsString<T> {
+    /// Gets the archived string.
+    #[inline]
+    pub fn as_str(&self) -> &str {
+        self.string.as_str()
+    }
+
+    /// Gets the underlying archived string.
+    #[inline]
+    pub fn as_archived_string(&self) -> &ArchivedString {
+        &self.string
+    }
+
+    /// Parses the archived string into a `T`.
+    #[inline]
+    pub fn parse(&self) -> Result<T, T::Err>
+    where
+        T: FromStr,
+    {
+        self.as_str().parse()
+    }
+}
+
+impl<T> AsRef<str> for ArchivedAsString<T> {
+    #[inline]
+    fn as_ref(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<T> Borrow<str> for ArchivedAsString<T> {
+    #[inline]
+    fn borrow(&self) -> &str {
+        self.as_str()
+    }
+}
+
+impl<T> Deref for ArchivedAsString<T> {
+    type Target = str;
+
+    #[inline]
+    fn deref(&self) -> &Self::Target {
+        self.as_str()
+    }
+}
+
+impl<T> fmt::Debug for ArchivedAsString<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self.as_str(), f)
+    }
+}
+
+impl<T> fmt::Display for ArchivedAsString<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self.as_str(), f)
+    }
+}
+
+impl<T> PartialEq for ArchivedAsString<T> {
+    #[inline]
+    fn eq(&self, other: &Self) -> bool {
+        self.as_str() == other.as_str()
+    }
+}
+
+impl<T> Eq for ArchivedAsString<T> {}
+
+impl<T> PartialEq<str> for ArchivedAsString<T> {
+    #[inline]
+    fn eq(&self, other: &str) -> bool {
+        self.as_str() == other
+    }
+}
+
+impl<T> PartialEq<&str> for ArchivedAsString<T> {
+    #[inline]
+    fn eq(&self, other: &&str) -> bool {
+        self.as_str() == *other
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::bytecheck::CheckBytes;
+    use std::ptr;
+
+    impl<T, C> CheckBytes<C> for ArchivedAsString<T>
+    where
+        ArchivedString: CheckBytes<C>,
+        C: ?Sized,
+    {
+        type Error = <ArchivedString as CheckBytes<C>>::Error;
+
+        #[inline]
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            ArchivedString::check_bytes(ptr::addr_of!((*value).string), context)?;
+            Ok(&*value)
+        }
+    }
+};
+
+/// The resolver for [`AsString`].
+pub struct AsStringResolver {
+    string: String,
+    resolver: StringResolver,
+}
+
+impl<T: fmt::Display> ArchiveWith<T> for AsString {
+    type Archived = ArchivedAsString<T>;
+    type Resolver = AsStringResolver;
+
+    #[inline]
+    unsafe fn resolve_with(_: &T, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.string);
+        ArchivedString::resolve_from_str(&resolver.string, pos + fp, resolver.resolver, fo);
+    }
+}
+
+impl<T, S> SerializeWith<T, S> for AsString
+where
+    T: fmt::Display,
+    S: Serializer + Fallible + ?Sized,
+{
+    #[inline]
+    fn serialize_with(field: &T, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let string = field.to_string();
+        let resolver = ArchivedString::serialize_from_str(&string, serializer)?;
+        Ok(AsStringResolver { string, resolver })
+    }
+}
+
+impl<T, D> DeserializeWith<ArchivedAsString<T>, T, D> for AsString
+where
+    T: FromStr,
+    D: Fallible + ?Sized,
+    D::Error: From<T::Err>,
+{
+    #[inline]
+    fn deserialize_with(field: &ArchivedAsString<T>, _: &mut D) -> Result<T, D::Error> {
+        Ok(field.parse()?)
+    }
+}
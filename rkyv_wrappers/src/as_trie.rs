@@ -0,0 +1,621 @@
+//! A wrapper that converts a `Vec` of string keys and values to an archived radix trie at
+//! serialization time.
+
+use crate::{
+    archived::to_usize,
+    as_hashmap::{sorted_entries, DuplicateKeyPolicy, Entries, FromEntries, LastWins},
+    as_indexmap::{Entry, EntryRef},
+};
+#[cfg(feature = "validation")]
+use rkyv::bytecheck::CheckBytes;
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    string::ArchivedString,
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Deserialize, Fallible, ScratchVec, Serialize,
+};
+use std::{fmt, iter::FusedIterator, marker::PhantomData, slice};
+
+/// A wrapper that converts a vector of string keys and values to and from an [`ArchivedTrie`],
+/// which supports prefix queries.
+///
+/// `ArchivedHashMap` can only find keys that match exactly. `AsTrie` sorts the entries by key in
+/// the serializer's scratch space and archives them alongside a radix trie over their bytes, so
+/// the archived trie can also find every key that starts with a prefix and the longest key that is
+/// a prefix of a query. Edge labels are read from the archived keys, so the trie adds a fixed
+/// amount of space per node no matter how long the keys are.
+///
+/// Entries that repeat a key are handled by the [`DuplicateKeyPolicy`] `P`. The default,
+/// [`LastWins`], matches collecting the vector into a `BTreeMap`.
+///
+/// Any container that implements [`Entries`] with `String` keys can be labeled `AsTrie`, and any
+/// collection that implements [`FromEntries`] can be deserialized from it. Deserializing produces
+/// one entry per unique key, sorted by key.
+///
+/// With the `validation` feature, checking an `ArchivedTrie` checks its entries and nodes and then
+/// verifies that the keys are sorted and that every node stays within the bounds of its parent, so
+/// queries on a checked archive never go out of bounds.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithTrie {
+///     #[with(rkyv_wrappers::as_trie::AsTrie)]
+///     pub words: Vec<(String, u32)>,
+/// }
+/// let original = StructWithTrie {
+///     words: vec![
+///         (String::from("tea"), 1),
+///         (String::from("ten"), 2),
+///         (String::from("to"), 3),
+///         (String::from("te"), 4),
+///     ],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithTrie>(&buffer)
+/// };
+/// assert_eq!(output.words.get("ten"), Some(&2));
+/// assert_eq!(output.words.get("t"), None);
+/// let keys = output.words.starts_with("te").map(|(k, _)| k).collect::<Vec<_>>();
+/// assert_eq!(keys, vec!["te", "tea", "ten"]);
+/// assert_eq!(output.words.longest_prefix("teapot"), Some(("tea", &1)));
+/// let deserialized: StructWithTrie = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized.words[0], (String::from("te"), 4));
+/// ```
+///
+/// # Panics
+///
+/// Serializing panics if the container has more than `u32::MAX / 2` entries.
+pub struct AsTrie<P = LastWins> {
+    _policy: PhantomData<P>,
+}
+
+/// An archived radix trie over string keys.
+///
+/// This is the archived version of vectors labeled [`AsTrie`].
+#[repr(C)]
+pub struct ArchivedTrie<V> {
+    entries: ArchivedVec<Entry<ArchivedString, V>>,
+    nodes: ArchivedVec<TrieNode>,
+}
+
+/// A node of an [`ArchivedTrie`].
+///
+/// Every node covers the range of sorted entries whose keys start with the bytes on the path from
+/// the root to the node, and its children are stored next to each other in order.
+#[derive(Debug)]
+#[cfg_attr(feature = "validation", derive(CheckBytes))]
+#[cfg_attr(feature = "validation", check_bytes(crate = "rkyv::bytecheck"))]
+#[repr(C)]
+pub struct TrieNode {
+    depth: Archived<u32>,
+    start: Archived<u32>,
+    end: Archived<u32>,
+    first_child: Archived<u32>,
+    child_count: Archived<u32>,
+}
+
+impl TrieNode {
+    /// The length of the prefix shared by every key under the node.
+    #[inline]
+    fn depth(&self) -> usize {
+        to_usize(self.depth)
+    }
+
+    #[inline]
+    fn start(&self) -> usize {
+        to_usize(self.start)
+    }
+
+    #[inline]
+    fn end(&self) -> usize {
+        to_usize(self.end)
+    }
+
+    #[inline]
+    fn children(&self) -> std::ops::Range<usize> {
+        let first_child = to_usize(self.first_child);
+        first_child..first_child + to_usize(self.child_count)
+    }
+}
+
+impl<V> ArchivedTrie<V> {
+    /// Gets the number of entries in the trie.
+    #[inline]
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    /// Returns whether the trie contains no entries.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    #[inline]
+    fn key(&self, index: usize) -> &[u8] {
+        self.entries[index].key.as_bytes()
+    }
+
+    /// Returns whether the node is the end of a key, which is always the first key in its range.
+    #[inline]
+    fn has_value(&self, node: &TrieNode) -> bool {
+        node.start() < node.end() && self.key(node.start()).len() == node.depth()
+    }
+
+    /// Finds the child of `node` whose edge starts with `byte`.
+    #[inline]
+    fn child(&self, node: &TrieNode, byte: u8) -> Option<&TrieNode> {
+        let children = &self.nodes[node.children()];
+        let depth = node.depth();
+        children
+            .binary_search_by(|child| self.key(child.start())[depth].cmp(&byte))
+            .ok()
+            .map(|i| &children[i])
+    }
+
+    /// Finds the shallowest node whose keys all start with `prefix`.
+    fn find(&self, prefix: &[u8]) -> Option<&TrieNode> {
+        let mut node = self.nodes.first()?;
+        while node.depth() < prefix.len() {
+            let child = self.child(node, prefix[node.depth()])?;
+            let end = child.depth().min(prefix.len());
+            if self.key(child.start())[node.depth()..end] != prefix[node.depth()..end] {
+                return None;
+            }
+            node = child;
+        }
+        Some(node)
+    }
+
+    /// Gets the key and value of the entry with the given key.
+    #[inline]
+    pub fn get_key_value(&self, key: &str) -> Option<(&str, &V)> {
+        let node = self.find(key.as_bytes())?;
+        if node.depth() == key.len() && self.has_value(node) {
+            let entry = &self.entries[node.start()];
+            Some((entry.key.as_str(), &entry.value))
+        } else {
+            None
+        }
+    }
+
+    /// Gets the value associated with the given key.
+    #[inline]
+    pub fn get(&self, key: &str) -> Option<&V> {
+        self.get_key_value(key).map(|(_, v)| v)
+    }
+
+    /// Returns whether a key is present in the trie.
+    #[inline]
+    pub fn contains_key(&self, key: &str) -> bool {
+        self.get_key_value(key).is_some()
+    }
+
+    /// Gets an iterator over the entries with keys that start with the given prefix, in key order.
+    #[inline]
+    pub fn starts_with(&self, prefix: &str) -> Iter<'_, V> {
+        let entries = match self.find(prefix.as_bytes()) {
+            Some(node) => &self.entries[node.start()..node.end()],
+            None => &[],
+        };
+        Iter {
+            inner: entries.iter(),
+        }
+    }
+
+    /// Gets the entry with the longest key that is a prefix of the given string.
+    pub fn longest_prefix(&self, s: &str) -> Option<(&str, &V)> {
+        let s = s.as_bytes();
+        let mut node = self.nodes.first()?;
+        let mut longest = None;
+        loop {
+            if self.has_value(node) {
+                longest = Some(node.start());
+            }
+            if node.depth() == s.len() {
+                break;
+            }
+            let child = match self.child(node, s[node.depth()]) {
+                Some(child) => child,
+                None => break,
+            };
+            if child.depth() > s.len()
+                || self.key(child.start())[node.depth()..child.depth()]
+                    != s[node.depth()..child.depth()]
+            {
+                break;
+            }
+            node = child;
+        }
+        longest.map(|index| {
+            let entry = &self.entries[index];
+            (entry.key.as_str(), &entry.value)
+        })
+    }
+
+    /// Gets the entries of the trie in key order.
+    #[inline]
+    pub fn as_slice(&self) -> &[Entry<ArchivedString, V>] {
+        self.entries.as_slice()
+    }
+
+    /// Gets an iterator over the entries of the trie in key order.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, V> {
+        Iter {
+            inner: self.entries.iter(),
+        }
+    }
+
+    /// Gets an iterator over the keys of the trie in order.
+    #[inline]
+    pub fn keys(&self) -> impl DoubleEndedIterator<Item = &str> + ExactSizeIterator {
+        self.entries.iter().map(|entry| entry.key.as_str())
+    }
+
+    /// Gets an iterator over the values of the trie in key order.
+    #[inline]
+    pub fn values(&self) -> impl DoubleEndedIterator<Item = &V> + ExactSizeIterator {
+        self.entries.iter().map(|entry| &entry.value)
+    }
+}
+
+impl<V: fmt::Debug> fmt::Debug for ArchivedTrie<V> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_map().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, V> IntoIterator for &'a ArchivedTrie<V> {
+    type Item = (&'a str, &'a V);
+    type IntoIter = Iter<'a, V>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the entries of an [`ArchivedTrie`] in key order.
+pub struct Iter<'a, V> {
+    inner: slice::Iter<'a, Entry<ArchivedString, V>>,
+}
+
+impl<'a, V> Iterator for Iter<'a, V> {
+    type Item = (&'a str, &'a V);
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next()
+            .map(|entry| (entry.key.as_str(), &entry.value))
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<V> DoubleEndedIterator for Iter<'_, V> {
+    #[inline]
+    fn next_back(&mut self) -> Option<Self::Item> {
+        self.inner
+            .next_back()
+            .map(|entry| (entry.key.as_str(), &entry.value))
+    }
+}
+
+impl<V> ExactSizeIterator for Iter<'_, V> {}
+impl<V> FusedIterator for Iter<'_, V> {}
+
+/// Errors that can occur while checking an [`ArchivedTrie`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum TrieError<E, N> {
+    /// An error occurred while checking the entries
+    EntriesError(E),
+    /// An error occurred while checking the nodes
+    NodesError(N),
+    /// The key of an entry is not greater than the key of the entry before it
+    Unsorted {
+        /// The position of the entry
+        index: usize,
+    },
+    /// The root node does not cover every entry
+    InvalidRoot,
+    /// A node is out of bounds or does not fit inside its parent
+    InvalidNode {
+        /// The position of the node
+        index: usize,
+    },
+}
+
+#[cfg(feature = "validation")]
+impl<E: fmt::Display, N: fmt::Display> fmt::Display for TrieError<E, N> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TrieError::EntriesError(e) => write!(f, "entries check error: {}", e),
+            TrieError::NodesError(e) => write!(f, "nodes check error: {}", e),
+            TrieError::Unsorted { index } => write!(
+                f,
+                "unsorted entries: entry {} is not greater than the entry before it",
+                index
+            ),
+            TrieError::InvalidRoot => write!(f, "invalid root node"),
+            TrieError::InvalidNode { index } => write!(f, "invalid node: node {}", index),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<E, N> std::error::Error for TrieError<E, N>
+where
+    E: std::error::Error + 'static,
+    N: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TrieError::EntriesError(e) => Some(e as &dyn std::error::Error),
+            TrieError::NodesError(e) => Some(e as &dyn std::error::Error),
+            TrieError::Unsorted { .. } | TrieError::InvalidRoot | TrieError::InvalidNode { .. } => {
+                None
+            }
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::validation::ArchiveContext;
+    use std::ptr;
+
+    impl<V, C> CheckBytes<C> for ArchivedTrie<V>
+    where
+        V: CheckBytes<C>,
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = TrieError<
+            <ArchivedVec<Entry<ArchivedString, V>> as CheckBytes<C>>::Error,
+            <ArchivedVec<TrieNode> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let entries = ArchivedVec::check_bytes(ptr::addr_of!((*value).entries), context)
+                .map_err(TrieError::EntriesError)?;
+            let nodes = ArchivedVec::check_bytes(ptr::addr_of!((*value).nodes), context)
+                .map_err(TrieError::NodesError)?;
+
+            for (i, pair) in entries.windows(2).enumerate() {
+                if pair[0].key >= pair[1].key {
+                    return Err(TrieError::Unsorted { index: i + 1 });
+                }
+            }
+
+            match nodes.first() {
+                Some(root)
+                    if root.depth() == 0 && root.start() == 0 && root.end() == entries.len() => {}
+                _ => return Err(TrieError::InvalidRoot),
+            }
+
+            let key = |index: usize| entries[index].key.as_bytes();
+            for (i, node) in nodes.iter().enumerate() {
+                let invalid = || TrieError::InvalidNode { index: i };
+
+                // Every key in the range must start with the same `depth` bytes, and since the keys
+                // are sorted it's enough to check the first and the last
+                if node.start() >= node.end() {
+                    if i != 0 {
+                        return Err(invalid());
+                    }
+                } else if node.end() > entries.len()
+                    || node.depth() > key(node.start()).len()
+                    || node.depth() > key(node.end() - 1).len()
+                    || key(node.start())[..node.depth()] != key(node.end() - 1)[..node.depth()]
+                {
+                    return Err(invalid());
+                }
+
+                // Children must come after their parent so that queries always terminate
+                let children = node.children();
+                if children.is_empty() {
+                    continue;
+                }
+                if children.start <= i || children.end > nodes.len() {
+                    return Err(invalid());
+                }
+                for child in &nodes[children] {
+                    if child.depth() <= node.depth()
+                        || child.start() < node.start()
+                        || child.end() > node.end()
+                    {
+                        return Err(invalid());
+                    }
+                }
+            }
+
+            Ok(&*value)
+        }
+    }
+};
+
+/// The resolver for [`AsTrie`].
+pub struct TrieResolver {
+    len: usize,
+    entries: VecResolver,
+    nodes_len: usize,
+    nodes: VecResolver,
+}
+
+/// A node being built, which archives as a [`TrieNode`].
+#[derive(Clone, Copy)]
+struct NodeData {
+    depth: u32,
+    start: u32,
+    end: u32,
+    first_child: u32,
+    child_count: u32,
+}
+
+impl Archive for NodeData {
+    type Archived = TrieNode;
+    type Resolver = ();
+
+    #[inline]
+    unsafe fn resolve(&self, pos: usize, _: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.depth);
+        self.depth.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.start);
+        self.start.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.end);
+        self.end.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.first_child);
+        self.first_child.resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.child_count);
+        self.child_count.resolve(pos + fp, (), fo);
+    }
+}
+
+impl<S: Fallible + ?Sized> Serialize<S> for NodeData {
+    #[inline]
+    fn serialize(&self, _: &mut S) -> Result<Self::Resolver, S::Error> {
+        Ok(())
+    }
+}
+
+impl<F: Entries<Key = String>, P> ArchiveWith<F> for AsTrie<P>
+where
+    F::Value: Archive,
+{
+    type Archived = ArchivedTrie<Archived<F::Value>>;
+    type Resolver = TrieResolver;
+
+    #[inline]
+    unsafe fn resolve_with(_: &F, pos: usize, resolver: Self::Resolver, out: *mut Self::Archived) {
+        let (fp, fo) = out_field!(out.entries);
+        ArchivedVec::resolve_from_len(resolver.len, pos + fp, resolver.entries, fo);
+        let (fp, fo) = out_field!(out.nodes);
+        ArchivedVec::resolve_from_len(resolver.nodes_len, pos + fp, resolver.nodes, fo);
+    }
+}
+
+impl<F, S, P> SerializeWith<F, S> for AsTrie<P>
+where
+    F: Entries<Key = String>,
+    F::Value: Serialize<S>,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+    P: DuplicateKeyPolicy<S::Error>,
+{
+    fn serialize_with(field: &F, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let len = field.len();
+        assert!(
+            len <= u32::MAX as usize / 2,
+            "AsTrie supports at most u32::MAX / 2 entries"
+        );
+
+        unsafe {
+            let entries = sorted_entries(len, |i| field.entry(i).0, serializer, P::on_duplicate)?;
+            let key = |index: usize| field.entry(entries[index].0).0.as_bytes();
+
+            // Each node without a value has at least two children, so there are at most two nodes
+            // per entry plus the root. Nodes are added breadth first so that the children of each
+            // node are next to each other.
+            let mut nodes = ScratchVec::<NodeData>::new(serializer, 2 * entries.len() + 1)?;
+            nodes.push(NodeData {
+                depth: 0,
+                start: 0,
+                end: entries.len() as u32,
+                first_child: 0,
+                child_count: 0,
+            });
+            let mut i = 0;
+            while i < nodes.len() {
+                let NodeData {
+                    depth, start, end, ..
+                } = nodes[i];
+                let depth = depth as usize;
+                let mut start = start as usize;
+                let end = end as usize;
+
+                // A key that ends at this node sorts before all of the keys that continue past it
+                if start < end && key(start).len() == depth {
+                    start += 1;
+                }
+                let first_child = nodes.len();
+                while start < end {
+                    let byte = key(start)[depth];
+                    let mut group_end = start + 1;
+                    while group_end < end && key(group_end)[depth] == byte {
+                        group_end += 1;
+                    }
+                    let child_depth = key(start)
+                        .iter()
+                        .zip(key(group_end - 1))
+                        .take_while(|(a, b)| a == b)
+                        .count();
+                    nodes.push(NodeData {
+                        depth: child_depth as u32,
+                        start: start as u32,
+                        end: group_end as u32,
+                        first_child: 0,
+                        child_count: 0,
+                    });
+                    start = group_end;
+                }
+                nodes[i].first_child = first_child as u32;
+                nodes[i].child_count = (nodes.len() - first_child) as u32;
+                i += 1;
+            }
+
+            let entries_resolver =
+                ArchivedVec::serialize_from_iter::<EntryRef<'_, String, F::Value>, _, _, _>(
+                    entries.iter().map(|&(k, v)| EntryRef {
+                        key: field.entry(k).0,
+                        value: field.entry(v).1,
+                    }),
+                    serializer,
+                )?;
+            let nodes_resolver = ArchivedVec::serialize_from_slice(&nodes, serializer)?;
+            let resolver = TrieResolver {
+                len: entries.len(),
+                entries: entries_resolver,
+                nodes_len: nodes.len(),
+                nodes: nodes_resolver,
+            };
+
+            nodes.free(serializer)?;
+            entries.free(serializer)?;
+
+            Ok(resolver)
+        }
+    }
+}
+
+impl<F: FromEntries<D, Key = String>, D: Fallible + ?Sized, P>
+    DeserializeWith<ArchivedTrie<Archived<F::Value>>, F, D> for AsTrie<P>
+where
+    F::Value: Archive,
+    Archived<F::Value>: Deserialize<F::Value, D>,
+{
+    #[inline]
+    fn deserialize_with(
+        field: &ArchivedTrie<Archived<F::Value>>,
+        deserializer: &mut D,
+    ) -> Result<F, D::Error> {
+        let entries = field
+            .iter()
+            .map(|(k, v)| Ok((k.to_string(), v.deserialize(deserializer)?)))
+            .collect::<Result<Vec<_>, _>>()?;
+        F::from_entries(entries)
+    }
+}
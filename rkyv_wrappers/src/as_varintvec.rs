@@ -0,0 +1,454 @@
+//! A wrapper that encodes a `Vec` of integers as variable-length integers at serialization time.
+
+use crate::{archived::to_usize, varint};
+use rkyv::{
+    out_field,
+    ser::{ScratchSpace, Serializer},
+    vec::{ArchivedVec, VecResolver},
+    with::{ArchiveWith, DeserializeWith, SerializeWith},
+    Archive, Archived, Fallible, ScratchVec,
+};
+use std::{convert::TryFrom, fmt, iter::FusedIterator, marker::PhantomData};
+
+/// A wrapper that encodes a vector of integers into an [`ArchivedVarIntVec`].
+///
+/// Vectors of mostly small integers archive as mostly zero bytes. `AsVarIntVec` stores each
+/// integer as an LEB128 variable-length integer instead, which takes one byte for values below
+/// 128, two bytes for values below 16384, and so on. Signed integers are zigzag encoded first so
+/// that small negative values stay small.
+///
+/// Since every integer takes a different number of bytes, the archived vector keeps the offset of
+/// every `STRIDE`th integer. Indexing decodes at most `STRIDE` integers from the closest offset
+/// before it, while iterating decodes the integers in order. Larger strides save more space, while
+/// smaller strides make random access faster. `STRIDE` must be between 1 and `u32::MAX`, and other
+/// strides fail to compile.
+///
+/// `AsVarIntVec` works with vectors of any [`VarInt`] type.
+///
+/// With the `validation` feature, checking an `ArchivedVarIntVec` decodes every integer once to
+/// make sure that all of them are valid and fit in the element type.
+///
+/// Example:
+///
+/// ```rust
+/// use rkyv::{archived_root, Deserialize, Infallible};
+/// use rkyv_wrappers::as_varintvec::AsVarIntVec;
+/// #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+/// struct StructWithIds {
+///     #[with(AsVarIntVec)]
+///     pub ids: Vec<u64>,
+///     #[with(AsVarIntVec<4>)]
+///     pub deltas: Vec<i64>,
+/// }
+/// let original = StructWithIds {
+///     ids: vec![3, 141, 59, 26, 5358979],
+///     deltas: vec![-1, 2, -300, 0, i64::MIN],
+/// };
+/// let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+/// let output = unsafe {
+///     archived_root::<StructWithIds>(&buffer)
+/// };
+/// assert_eq!(output.ids.get(1), Some(141));
+/// assert_eq!(output.ids.iter().sum::<u64>(), 5359208);
+/// assert_eq!(output.deltas.get(2), Some(-300));
+/// let deserialized: StructWithIds = output.deserialize(&mut Infallible).unwrap();
+/// assert_eq!(deserialized, original);
+/// ```
+///
+/// A stride of zero does not compile:
+///
+/// ```compile_fail
+/// use rkyv_wrappers::as_varintvec::AsVarIntVec;
+///
+/// #[derive(rkyv::Archive, rkyv::Serialize)]
+/// struct StructWithIds {
+///     #[with(AsVarIntVec<0>)]
+///     pub ids: Vec<u64>,
+/// }
+/// let buffer = rkyv::to_bytes::<_, 256>(&StructWithIds { ids: Vec::new() });
+/// ```
+pub struct AsVarIntVec<const STRIDE: usize = 32>;
+
+impl<const STRIDE: usize> AsVarIntVec<STRIDE> {
+    /// Fails to compile when `STRIDE` is zero or larger than `u32::MAX`
+    const VALID_STRIDE: () = assert!(
+        STRIDE > 0 && STRIDE <= u32::MAX as usize,
+        "AsVarIntVec requires a stride between 1 and u32::MAX"
+    );
+}
+
+/// An integer type that can be stored in an [`ArchivedVarIntVec`].
+pub trait VarInt: Copy {
+    /// Converts the integer to the `u64` that gets encoded.
+    fn to_varint(self) -> u64;
+
+    /// Converts a decoded `u64` back to an integer.
+    ///
+    /// Returns `None` if the value does not fit in the integer type.
+    fn from_varint(value: u64) -> Option<Self>;
+}
+
+macro_rules! impl_varint_unsigned {
+    ($($ty:ty),*) => {
+        $(
+            impl VarInt for $ty {
+                #[inline]
+                fn to_varint(self) -> u64 {
+                    self as u64
+                }
+
+                #[inline]
+                fn from_varint(value: u64) -> Option<Self> {
+                    Self::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_varint_unsigned!(u8, u16, u32, u64);
+
+macro_rules! impl_varint_signed {
+    ($($ty:ty),*) => {
+        $(
+            impl VarInt for $ty {
+                #[inline]
+                fn to_varint(self) -> u64 {
+                    let value = self as i64;
+                    ((value << 1) ^ (value >> 63)) as u64
+                }
+
+                #[inline]
+                fn from_varint(value: u64) -> Option<Self> {
+                    let value = (value >> 1) as i64 ^ -((value & 1) as i64);
+                    Self::try_from(value).ok()
+                }
+            }
+        )*
+    };
+}
+
+impl_varint_signed!(i8, i16, i32, i64);
+
+/// An archived vector of variable-length integers.
+///
+/// This is the archived version of vectors labeled [`AsVarIntVec`]. Integers are decoded when they
+/// are accessed.
+///
+/// Accessing the integers of an archive that was not validated panics if the encoded data is
+/// malformed.
+#[repr(C)]
+pub struct ArchivedVarIntVec<T> {
+    len: Archived<u32>,
+    stride: Archived<u32>,
+    offsets: ArchivedVec<Archived<u32>>,
+    data: ArchivedVec<u8>,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: VarInt> ArchivedVarIntVec<T> {
+    /// Gets the number of integers in the vector.
+    #[inline]
+    pub fn len(&self) -> usize {
+        to_usize(self.len)
+    }
+
+    /// Returns whether the vector contains no integers.
+    #[inline]
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Gets the number of integers between each stored offset.
+    #[inline]
+    pub fn stride(&self) -> usize {
+        to_usize(self.stride)
+    }
+
+    /// Gets the integer at the given position.
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.len() {
+            return None;
+        }
+        let mut iter = Iter {
+            data: self.data.as_slice(),
+            pos: to_usize(self.offsets[index / self.stride()]),
+            index: index - index % self.stride(),
+            len: self.len(),
+            _phantom: PhantomData,
+        };
+        iter.nth(index % self.stride())
+    }
+
+    /// Gets the number of bytes that the integers are encoded into.
+    #[inline]
+    pub fn encoded_len(&self) -> usize {
+        self.data.len()
+    }
+
+    /// Gets an iterator over the integers in the vector.
+    #[inline]
+    pub fn iter(&self) -> Iter<'_, T> {
+        Iter {
+            data: self.data.as_slice(),
+            pos: 0,
+            index: 0,
+            len: self.len(),
+            _phantom: PhantomData,
+        }
+    }
+}
+
+impl<T: VarInt + fmt::Debug> fmt::Debug for ArchivedVarIntVec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.iter()).finish()
+    }
+}
+
+impl<'a, T: VarInt> IntoIterator for &'a ArchivedVarIntVec<T> {
+    type Item = T;
+    type IntoIter = Iter<'a, T>;
+
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+/// An iterator over the integers of an [`ArchivedVarIntVec`].
+pub struct Iter<'a, T> {
+    data: &'a [u8],
+    pos: usize,
+    index: usize,
+    len: usize,
+    _phantom: PhantomData<T>,
+}
+
+impl<T: VarInt> Iterator for Iter<'_, T> {
+    type Item = T;
+
+    #[inline]
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.index >= self.len {
+            return None;
+        }
+        self.index += 1;
+        let value = varint::decode(self.data, &mut self.pos)
+            .and_then(T::from_varint)
+            .expect("invalid variable-length integer");
+        Some(value)
+    }
+
+    #[inline]
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.len - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T: VarInt> ExactSizeIterator for Iter<'_, T> {}
+impl<T: VarInt> FusedIterator for Iter<'_, T> {}
+
+/// Errors that can occur while checking an [`ArchivedVarIntVec`].
+#[cfg(feature = "validation")]
+#[derive(Debug)]
+pub enum VarIntVecError<O, D> {
+    /// An error occurred while checking the offsets
+    OffsetsError(O),
+    /// An error occurred while checking the encoded integers
+    DataError(D),
+    /// The stride is zero or the number of offsets does not match the number of integers
+    InvalidOffsets,
+    /// An offset does not point to the integer it should
+    InvalidOffset {
+        /// The position of the offset
+        offset: usize,
+    },
+    /// An integer is truncated or does not fit in the element type
+    InvalidValue {
+        /// The position of the integer
+        index: usize,
+    },
+    /// There are bytes left over after the last integer
+    TrailingBytes,
+}
+
+#[cfg(feature = "validation")]
+impl<O: fmt::Display, D: fmt::Display> fmt::Display for VarIntVecError<O, D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VarIntVecError::OffsetsError(e) => write!(f, "offsets check error: {}", e),
+            VarIntVecError::DataError(e) => write!(f, "data check error: {}", e),
+            VarIntVecError::InvalidOffsets => {
+                write!(f, "invalid offsets: stride or count is wrong")
+            }
+            VarIntVecError::InvalidOffset { offset } => {
+                write!(f, "invalid offset: offset {}", offset)
+            }
+            VarIntVecError::InvalidValue { index } => {
+                write!(f, "invalid encoded integer: integer {}", index)
+            }
+            VarIntVecError::TrailingBytes => write!(f, "trailing bytes after last integer"),
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<O, D> std::error::Error for VarIntVecError<O, D>
+where
+    O: std::error::Error + 'static,
+    D: std::error::Error + 'static,
+{
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            VarIntVecError::OffsetsError(e) => Some(e as &dyn std::error::Error),
+            VarIntVecError::DataError(e) => Some(e as &dyn std::error::Error),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+const _: () = {
+    use rkyv::{bytecheck::CheckBytes, validation::ArchiveContext};
+    use std::ptr;
+
+    impl<T: VarInt, C> CheckBytes<C> for ArchivedVarIntVec<T>
+    where
+        C: ArchiveContext + ?Sized,
+        C::Error: std::error::Error,
+    {
+        type Error = VarIntVecError<
+            <ArchivedVec<Archived<u32>> as CheckBytes<C>>::Error,
+            <ArchivedVec<u8> as CheckBytes<C>>::Error,
+        >;
+
+        unsafe fn check_bytes<'a>(
+            value: *const Self,
+            context: &mut C,
+        ) -> Result<&'a Self, Self::Error> {
+            let offsets = ArchivedVec::check_bytes(ptr::addr_of!((*value).offsets), context)
+                .map_err(VarIntVecError::OffsetsError)?;
+            let data = ArchivedVec::check_bytes(ptr::addr_of!((*value).data), context)
+                .map_err(VarIntVecError::DataError)?;
+            let value = &*value;
+
+            let len = value.len();
+            let stride = value.stride();
+            // Both lengths come from the archive, so adding them could overflow
+            if stride == 0 || offsets.len() != len / stride + (len % stride != 0) as usize {
+                return Err(VarIntVecError::InvalidOffsets);
+            }
+
+            let data = data.as_slice();
+            let mut pos = 0;
+            for index in 0..len {
+                if index % stride == 0 {
+                    let offset = index / stride;
+                    if to_usize(offsets[offset]) != pos {
+                        return Err(VarIntVecError::InvalidOffset { offset });
+                    }
+                }
+                varint::decode(data, &mut pos)
+                    .and_then(T::from_varint)
+                    .ok_or(VarIntVecError::InvalidValue { index })?;
+            }
+            if pos != data.len() {
+                return Err(VarIntVecError::TrailingBytes);
+            }
+
+            Ok(value)
+        }
+    }
+};
+
+/// The resolver for [`AsVarIntVec`].
+pub struct VarIntVecResolver {
+    offsets_len: usize,
+    offsets: VecResolver,
+    data_len: usize,
+    data: VecResolver,
+}
+
+impl<T: VarInt, const STRIDE: usize> ArchiveWith<Vec<T>> for AsVarIntVec<STRIDE> {
+    type Archived = ArchivedVarIntVec<T>;
+    type Resolver = VarIntVecResolver;
+
+    #[inline]
+    unsafe fn resolve_with(
+        field: &Vec<T>,
+        pos: usize,
+        resolver: Self::Resolver,
+        out: *mut Self::Archived,
+    ) {
+        let () = Self::VALID_STRIDE;
+        let (fp, fo) = out_field!(out.len);
+        (field.len() as u32).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.stride);
+        (STRIDE as u32).resolve(pos + fp, (), fo);
+        let (fp, fo) = out_field!(out.offsets);
+        ArchivedVec::resolve_from_len(resolver.offsets_len, pos + fp, resolver.offsets, fo);
+        let (fp, fo) = out_field!(out.data);
+        ArchivedVec::resolve_from_len(resolver.data_len, pos + fp, resolver.data, fo);
+    }
+}
+
+/// # Panics
+///
+/// Panics if the vector has more than `u32::MAX` integers or encodes to more than `u32::MAX`
+/// bytes.
+impl<T, S, const STRIDE: usize> SerializeWith<Vec<T>, S> for AsVarIntVec<STRIDE>
+where
+    T: VarInt,
+    S: ScratchSpace + Serializer + Fallible + ?Sized,
+{
+    fn serialize_with(field: &Vec<T>, serializer: &mut S) -> Result<Self::Resolver, S::Error> {
+        let () = Self::VALID_STRIDE;
+        assert!(
+            field.len() <= u32::MAX as usize,
+            "AsVarIntVec supports at most u32::MAX integers"
+        );
+
+        unsafe {
+            // Measure the integers first so they can be encoded in one go
+            let mut offsets = ScratchVec::new(serializer, (field.len() + STRIDE - 1) / STRIDE)?;
+            let mut data_len = 0;
+            for (i, value) in field.iter().enumerate() {
+                if i % STRIDE == 0 {
+                    offsets.push(data_len as u32);
+                }
+                data_len += varint::encoded_len(value.to_varint());
+            }
+            assert!(
+                data_len <= u32::MAX as usize,
+                "AsVarIntVec supports at most u32::MAX bytes of encoded integers"
+            );
+
+            let mut data = ScratchVec::<u8>::new(serializer, data_len)?;
+            for value in field.iter() {
+                varint::encode(value.to_varint(), |byte| data.push(byte));
+            }
+
+            let resolver = VarIntVecResolver {
+                offsets_len: offsets.len(),
+                offsets: ArchivedVec::serialize_from_slice(&offsets, serializer)?,
+                data_len: data.len(),
+                data: ArchivedVec::serialize_from_slice(&data, serializer)?,
+            };
+
+            data.free(serializer)?;
+            offsets.free(serializer)?;
+
+            Ok(resolver)
+        }
+    }
+}
+
+impl<T: VarInt, D: Fallible + ?Sized, const STRIDE: usize>
+    DeserializeWith<ArchivedVarIntVec<T>, Vec<T>, D> for AsVarIntVec<STRIDE>
+{
+    #[inline]
+    fn deserialize_with(field: &ArchivedVarIntVec<T>, _: &mut D) -> Result<Vec<T>, D::Error> {
+        Ok(field.iter().collect())
+    }
+}
@@ -8,6 +8,8 @@ use std::marker::PhantomData;
 
 /// A wrapper that allows for changing the generic type of a PhantomData<T>.
 /// 
+/// With the `validation` feature, the archived `PhantomData<NT>` is zero-sized and always valid.
+///
 /// Example:
 /// 
 /// ```rust
@@ -1,11 +1,141 @@
 //! Accessory and community-contributed wrapper types for [rkyv](https://github.com/rkyv/rkyv).
+//!
+//! With the `validation` feature, the archived types produced by every wrapper implement
+//! `CheckBytes`, so archives that use them can be checked with `check_archived_root` as long as
+//! the containing type is archived with `#[archive(check_bytes)]`. See the documentation of each
+//! wrapper for what its checks guarantee.
+//!
+//! ## Serializer errors
+//!
+//! Some wrappers reject values that they can't archive, like an unsorted vector labeled
+//! [`AsDeltaEncoded`](as_deltaencoded::AsDeltaEncoded). They return the error through the
+//! serializer, so the serializer's error type must implement `From` for the wrapper's error type.
+//! The serializers in rkyv can't hold these errors, so `rkyv::to_bytes` can't serialize types that
+//! use these wrappers. [`BoxedErrorSerializer`] is an `AllocSerializer` whose error type is
+//! `Box<dyn Error>`, which can hold the errors of every wrapper. Because the box is not `Send` or
+//! `Sync`, those errors can't be sent to other threads:
+//!
+//! ```rust
+//! use rkyv::ser::Serializer;
+//! use rkyv_wrappers::{
+//!     as_deltaencoded::{AsDeltaEncoded, UnsortedError},
+//!     BoxedErrorSerializer,
+//! };
+//!
+//! #[derive(rkyv::Archive, rkyv::Serialize)]
+//! struct StructWithTimestamps {
+//!     #[with(AsDeltaEncoded)]
+//!     pub timestamps: Vec<u64>,
+//! }
+//! let unsorted = StructWithTimestamps {
+//!     timestamps: vec![3, 1, 2],
+//! };
+//! let mut serializer = BoxedErrorSerializer::default();
+//! let error = serializer.serialize_value(&unsorted).unwrap_err();
+//! assert_eq!(error.downcast_ref::<UnsortedError>().unwrap().index, 1);
+//! ```
 
 #![deny(rustdoc::broken_intra_doc_links)]
 #![deny(missing_docs)]
 #![deny(rustdoc::missing_crate_level_docs)]
 
+use rkyv::{
+    ser::{serializers::AllocSerializer, ScratchSpace, Serializer, SharedSerializeRegistry},
+    AlignedVec, Fallible,
+};
+use std::{alloc::Layout, error::Error, ptr::NonNull};
+
+pub mod as_bitvec;
+pub mod as_btreemap;
+pub mod as_columnar;
+pub mod as_convert;
+pub mod as_deltaencoded;
+pub mod as_f16;
+pub mod as_fixedpoint;
+pub mod as_frontcodedstrings;
 pub mod as_hashmap;
+pub mod as_hashset;
+pub mod as_indexmap;
+pub mod as_internedstrings;
+pub mod as_keyedmap;
+pub mod as_multimap;
+pub mod as_quantizedu8;
+pub mod as_runlength;
+pub mod as_sortedvec;
+pub mod as_sparsevec;
+pub mod as_string;
+pub mod as_trie;
+pub mod as_varintvec;
+#[allow(clippy::tabs_in_doc_comments)]
 pub mod custom_phantom;
 
+mod archived;
+mod varint;
+
+/// An `AllocSerializer` whose error type is `Box<dyn Error>`.
+///
+/// It can serialize types that use wrappers which return their own errors. It holds any error that
+/// implements `Error + 'static`: the errors of the wrappers in this crate, the errors that
+/// conversion and parsing wrappers pass through, and the errors of the `AllocSerializer` itself.
+/// The box is not `Send` or `Sync`, so its errors can't be sent to other threads. See
+/// [Serializer errors](crate#serializer-errors).
+#[derive(Default)]
+pub struct BoxedErrorSerializer {
+    inner: AllocSerializer<4096>,
+}
+
+impl BoxedErrorSerializer {
+    /// Consumes the serializer and returns the bytes that were written.
+    #[inline]
+    pub fn into_inner(self) -> AlignedVec {
+        self.inner.into_serializer().into_inner()
+    }
+}
+
+impl Fallible for BoxedErrorSerializer {
+    type Error = Box<dyn Error>;
+}
+
+impl Serializer for BoxedErrorSerializer {
+    #[inline]
+    fn pos(&self) -> usize {
+        self.inner.pos()
+    }
+
+    #[inline]
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Self::Error> {
+        Ok(self.inner.write(bytes)?)
+    }
+}
+
+impl ScratchSpace for BoxedErrorSerializer {
+    #[inline]
+    unsafe fn push_scratch(&mut self, layout: Layout) -> Result<NonNull<[u8]>, Self::Error> {
+        Ok(self.inner.push_scratch(layout)?)
+    }
+
+    #[inline]
+    unsafe fn pop_scratch(&mut self, ptr: NonNull<u8>, layout: Layout) -> Result<(), Self::Error> {
+        Ok(self.inner.pop_scratch(ptr, layout)?)
+    }
+}
+
+impl SharedSerializeRegistry for BoxedErrorSerializer {
+    #[inline]
+    fn get_shared_ptr(&self, value: *const u8) -> Option<usize> {
+        self.inner.get_shared_ptr(value)
+    }
+
+    #[inline]
+    fn add_shared_ptr(&mut self, value: *const u8, pos: usize) -> Result<(), Self::Error> {
+        Ok(self.inner.add_shared_ptr(value, pos)?)
+    }
+}
+
+// Lets the derive macros refer to this crate as `::rkyv_wrappers` from inside it
+#[cfg(test)]
+extern crate self as rkyv_wrappers;
+
 #[cfg(test)]
+#[allow(missing_docs)]
 pub mod tests;
@@ -1,29 +1,3329 @@
-pub mod as_hashmap {
+pub mod util {
+    use rkyv::Fallible;
+    use std::error::Error;
+
+    /// Overwrites the root object at the end of `bytes` with `0xff` bytes.
+    #[cfg(feature = "validation")]
+    pub fn corrupt_root<T>(bytes: &mut [u8]) {
+        let len = bytes.len();
+        for b in bytes[len - std::mem::size_of::<T>()..].iter_mut() {
+            *b = 0xff;
+        }
+    }
+
+    /// A serializer whose error type can hold the errors returned by wrappers.
+    pub type TestSerializer = crate::BoxedErrorSerializer;
+
+    /// A deserializer whose error type can hold the errors returned by wrappers.
+    pub struct TestDeserializer;
+
+    impl Fallible for TestDeserializer {
+        type Error = Box<dyn Error>;
+    }
+}
+
+pub mod as_bitvec {
     #[test]
-    fn struct_with_hashmap() {
-        use rkyv::{
-            archived_root,
-            ser::{serializers::AllocSerializer, Serializer},
-            Deserialize, Infallible,
+    fn struct_with_bitvec() {
+        use super::util::TestDeserializer;
+        use crate::as_bitvec::AsBitVec;
+        use rkyv::{archived_root, Deserialize};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithFlags {
+            #[with(AsBitVec)]
+            pub flags: Vec<bool>,
+            #[with(AsBitVec)]
+            pub exact: Vec<bool>,
+            #[with(AsBitVec)]
+            pub empty: Vec<bool>,
+            #[with(AsBitVec)]
+            pub array: [bool; 70],
+        }
+
+        let mut array = [false; 70];
+        array[0] = true;
+        array[63] = true;
+        array[64] = true;
+        array[69] = true;
+        let original = StructWithFlags {
+            flags: (0..1000).map(|i| i % 3 == 0 || i % 7 == 0).collect(),
+            exact: vec![true; 128],
+            empty: Vec::new(),
+            array,
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithFlags>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.flags).unwrap();
+        assert!(buffer.len() < plain.len() / 4);
+
+        assert_eq!(output.flags.len(), 1000);
+        assert_eq!(output.flags.as_words().len(), 16);
+        for (i, &flag) in original.flags.iter().enumerate() {
+            assert_eq!(output.flags.get(i), Some(flag));
+        }
+        assert_eq!(output.flags.get(1000), None);
+        assert_eq!(
+            output.flags.count_ones(),
+            original.flags.iter().filter(|&&f| f).count()
+        );
+        assert_eq!(output.flags.count_ones() + output.flags.count_zeros(), 1000);
+        assert!(output.flags.iter().eq(original.flags.iter().copied()));
+        assert!(output
+            .flags
+            .iter()
+            .rev()
+            .eq(original.flags.iter().rev().copied()));
+
+        assert_eq!(output.exact.count_ones(), 128);
+        assert_eq!(output.exact.as_words().len(), 2);
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.iter().count(), 0);
+        assert_eq!(output.array.count_ones(), 4);
+        assert_eq!(output.array.get(69), Some(true));
+
+        let deserialized: StructWithFlags = output.deserialize(&mut TestDeserializer).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn bitvec_array_length() {
+        use super::util::TestDeserializer;
+        use crate::{as_bitvec::AsBitVec, as_hashmap::EntryCountError};
+        use rkyv::{archived_root, with::DeserializeWith};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithFlags {
+            #[with(AsBitVec)]
+            pub flags: Vec<bool>,
+        }
+
+        let original = StructWithFlags {
+            flags: vec![true, false, true],
         };
+        let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithFlags>(&buffer) };
+
+        let array: [bool; 3] =
+            AsBitVec::deserialize_with(&output.flags, &mut TestDeserializer).unwrap();
+        assert_eq!(array, [true, false, true]);
+
+        let error = <AsBitVec as DeserializeWith<_, [bool; 2], _>>::deserialize_with(
+            &output.flags,
+            &mut TestDeserializer,
+        )
+        .unwrap_err();
+        let error = error.downcast_ref::<EntryCountError>().unwrap();
+        assert_eq!((error.expected, error.found), (2, 3));
+
+        let error = <AsBitVec as DeserializeWith<_, [bool; 4], _>>::deserialize_with(
+            &output.flags,
+            &mut TestDeserializer,
+        )
+        .unwrap_err();
+        let error = error.downcast_ref::<EntryCountError>().unwrap();
+        assert_eq!((error.expected, error.found), (4, 3));
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_bitvec() {
+        use super::util::corrupt_root;
+        use crate::as_bitvec::AsBitVec;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithFlags {
+            #[with(AsBitVec)]
+            pub flags: Vec<bool>,
+        }
+
+        // Packs into the words 0xa1b2_c3d4_e5f6_0718 and 0x35
+        let first = 0xa1b2_c3d4_e5f6_0718u64;
+        let original = StructWithFlags {
+            flags: (0..70)
+                .map(|i| {
+                    if i < 64 {
+                        first >> i & 1 == 1
+                    } else {
+                        0x35u64 >> (i - 64) & 1 == 1
+                    }
+                })
+                .collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithFlags>(&buffer).unwrap();
+        assert_eq!(output.flags.count_ones(), 31 + 4);
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithFlags>(&mut corrupted);
+        assert!(check_archived_root::<StructWithFlags>(&corrupted).is_err());
+
+        // Setting the highest bit of the last word sets a bit past the end
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(8)
+            .position(|w| w == first.to_ne_bytes())
+            .unwrap();
+        corrupted[pos + 8..pos + 16].copy_from_slice(&(0x35u64 | 1 << 63).to_ne_bytes());
+        assert!(check_archived_root::<StructWithFlags>(&corrupted).is_err());
+    }
+}
+
+pub mod as_btreemap {
+    #[test]
+    fn struct_with_btreemap() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+        use std::collections::BTreeMap;
 
         #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
-        struct StructWithHashMap {
-            #[with(crate::as_hashmap::AsHashMap)]
-            pub hash_map: Vec<(u32, String)>,
+        struct StructWithBTreeMap {
+            #[with(crate::as_btreemap::AsBTreeMap)]
+            pub btree_map: Vec<(u32, u32)>,
         }
 
-        let mut serializer = AllocSerializer::<4096>::default();
-        let original = StructWithHashMap {
-            hash_map: vec![(1, String::from("a")), (2, String::from("b"))],
+        // Large enough to require inner nodes, with every key repeated
+        let original = StructWithBTreeMap {
+            btree_map: (0..10_000u32).map(|i| ((i * 7919) % 5000, i)).collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let expected = original
+            .btree_map
+            .iter()
+            .copied()
+            .collect::<BTreeMap<_, _>>();
+        let output = unsafe { archived_root::<StructWithBTreeMap>(&buffer) };
+        assert_eq!(output.btree_map.len(), expected.len());
+        for (k, v) in expected.iter() {
+            assert_eq!(output.btree_map.get(k), Some(v));
+        }
+        assert!(output.btree_map.get(&5000).is_none());
+
+        let deserialized: StructWithBTreeMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(
+            deserialized.btree_map,
+            expected.into_iter().collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn btreemap_presorted() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithBTreeMap {
+            #[with(crate::as_btreemap::AsBTreeMap)]
+            pub btree_map: Vec<(String, u32)>,
+        }
+
+        let original = StructWithBTreeMap {
+            btree_map: (0..1000u32).map(|i| (format!("{:04}", i), i)).collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithBTreeMap>(&buffer) };
+        assert_eq!(output.btree_map.len(), 1000);
+        assert_eq!(output.btree_map.get("0042"), Some(&42));
+
+        let deserialized: StructWithBTreeMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn btreemap_first_wins() {
+        use crate::as_hashmap::FirstWins;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithBTreeMap {
+            #[with(crate::as_btreemap::AsBTreeMap<FirstWins>)]
+            pub btree_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithBTreeMap {
+            btree_map: vec![
+                (2, String::from("a")),
+                (1, String::from("b")),
+                (2, String::from("c")),
+            ],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithBTreeMap>(&buffer) };
+        let deserialized: StructWithBTreeMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(
+            deserialized.btree_map,
+            vec![(1, String::from("b")), (2, String::from("a"))]
+        );
+    }
+
+    #[test]
+    fn btreemap_rejects_duplicate_keys() {
+        use super::util::TestSerializer;
+        use crate::as_hashmap::{DuplicateKeyError, RejectDuplicates};
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithBTreeMap {
+            #[with(crate::as_btreemap::AsBTreeMap<RejectDuplicates>)]
+            pub btree_map: Vec<(u32, String)>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithBTreeMap {
+            btree_map: vec![
+                (3, String::from("a")),
+                (1, String::from("b")),
+                (3, String::from("c")),
+            ],
+        };
+        let error = serializer.serialize_value(&original).unwrap_err();
+        let error = error.downcast_ref::<DuplicateKeyError>().unwrap();
+        assert_eq!(error.first, 0);
+        assert_eq!(error.duplicate, 2);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_btreemap() {
+        use super::util::corrupt_root;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithBTreeMap {
+            #[with(crate::as_btreemap::AsBTreeMap)]
+            pub btree_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithBTreeMap {
+            btree_map: (0..1000u32).rev().map(|i| (i, i.to_string())).collect(),
+        };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithBTreeMap>(&buffer).unwrap();
+        assert_eq!(output.btree_map.get(&42).unwrap(), &"42");
+
+        corrupt_root::<ArchivedStructWithBTreeMap>(&mut buffer);
+        assert!(check_archived_root::<StructWithBTreeMap>(&buffer).is_err());
+    }
+}
+
+#[cfg(feature = "derive")]
+pub mod as_columnar {
+    use crate::as_columnar::Columnar;
+
+    #[derive(Columnar, Debug, PartialEq)]
+    #[cfg_attr(feature = "validation", columnar(check_bytes))]
+    pub struct Reading {
+        pub sensor: u16,
+        pub value: f64,
+        pub context: Option<String>,
+        pub row: u8,
+    }
+
+    fn readings(len: usize) -> Vec<Reading> {
+        (0..len)
+            .map(|i| Reading {
+                sensor: (i % 3) as u16,
+                value: i as f64 * 0.5,
+                context: if i % 4 == 0 {
+                    Some(format!("reading {}", i))
+                } else {
+                    None
+                },
+                row: i as u8,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn struct_with_columnar() {
+        use crate::as_columnar::AsColumnar;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithReadings {
+            #[with(AsColumnar)]
+            pub readings: Vec<Reading>,
+            #[with(AsColumnar)]
+            pub empty: Vec<Reading>,
+        }
+
+        let original = StructWithReadings {
+            readings: readings(100),
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithReadings>(&buffer) };
+
+        assert_eq!(output.readings.len(), 100);
+        assert_eq!(output.readings.value.len(), 100);
+        assert_eq!(output.readings.value.iter().sum::<f64>(), 2475.0);
+        assert_eq!(
+            output.readings.sensor.iter().filter(|&&s| s == 2).count(),
+            33
+        );
+
+        let row = output.readings.row(8).unwrap();
+        assert_eq!(*row.sensor, 2);
+        assert_eq!(*row.value, 4.0);
+        assert_eq!(row.context.as_deref(), Some("reading 8"));
+        assert_eq!(*row.row, 8);
+        assert!(output.readings.row(100).is_none());
+
+        for (row, reading) in output.readings.rows().zip(original.readings.iter()) {
+            assert_eq!(*row.sensor, reading.sensor);
+            assert_eq!(row.context.as_deref(), reading.context.as_deref());
+        }
+        assert_eq!(*output.readings.rows().next_back().unwrap().row, 99);
+        assert_eq!(output.readings.rows().len(), 100);
+
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.rows().count(), 0);
+
+        let deserialized: StructWithReadings = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_columnar() {
+        use super::util::corrupt_root;
+        use crate::as_columnar::AsColumnar;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithReadings {
+            #[with(AsColumnar)]
+            pub readings: Vec<Reading>,
+        }
+
+        let original = StructWithReadings {
+            readings: readings(10),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithReadings>(&buffer).unwrap();
+        assert_eq!(
+            output.readings.row(4).unwrap().context.as_deref(),
+            Some("reading 4")
+        );
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithReadings>(&mut corrupted);
+        assert!(check_archived_root::<StructWithReadings>(&corrupted).is_err());
+
+        // Each column is a relative pointer followed by a length, so this shortens `value`
+        let mut corrupted = buffer.clone();
+        let root = corrupted.len() - std::mem::size_of::<ArchivedStructWithReadings>();
+        corrupted[root + 12..root + 16].copy_from_slice(&9u32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithReadings>(&corrupted).is_err());
+    }
+}
+
+pub mod as_convert {
+    use std::{convert::TryFrom, fmt, net::Ipv4Addr};
+
+    /// A hostname that archives as its `String`
+    #[derive(Debug, PartialEq)]
+    pub struct Hostname {
+        pub labels: Vec<String>,
+    }
+
+    impl From<&Hostname> for String {
+        fn from(hostname: &Hostname) -> Self {
+            hostname.labels.join(".")
+        }
+    }
+
+    impl From<String> for Hostname {
+        fn from(hostname: String) -> Self {
+            Hostname {
+                labels: hostname.split('.').map(String::from).collect(),
+            }
+        }
+    }
+
+    /// An IPv4 address that archives as its octets, which can't be a multicast address
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+    #[cfg_attr(feature = "validation", archive(check_bytes))]
+    pub struct UnicastOctets(pub [u8; 4]);
+
+    #[derive(Debug)]
+    pub struct MulticastError;
+
+    impl fmt::Display for MulticastError {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "multicast addresses are not allowed")
+        }
+    }
+
+    impl std::error::Error for MulticastError {}
+
+    impl TryFrom<&Ipv4Addr> for UnicastOctets {
+        type Error = MulticastError;
+
+        fn try_from(addr: &Ipv4Addr) -> Result<Self, Self::Error> {
+            if addr.is_multicast() {
+                Err(MulticastError)
+            } else {
+                Ok(UnicastOctets(addr.octets()))
+            }
+        }
+    }
+
+    impl TryFrom<UnicastOctets> for Ipv4Addr {
+        type Error = MulticastError;
+
+        fn try_from(octets: UnicastOctets) -> Result<Self, Self::Error> {
+            let addr = Ipv4Addr::from(octets.0);
+            if addr.is_multicast() {
+                Err(MulticastError)
+            } else {
+                Ok(addr)
+            }
+        }
+    }
+
+    #[test]
+    fn struct_with_convert() {
+        use super::util::{TestDeserializer, TestSerializer};
+        use crate::as_convert::{AsConvert, AsTryConvert};
+        use rkyv::{archived_root, ser::Serializer, Deserialize};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct Server {
+            #[with(AsConvert<String>)]
+            pub hostname: Hostname,
+            #[with(AsConvert<String>)]
+            pub fallback: Hostname,
+            #[with(AsTryConvert<UnicastOctets>)]
+            pub addr: Ipv4Addr,
+        }
+
+        let original = Server {
+            hostname: Hostname::from("example.com".to_string()),
+            fallback: Hostname::from("backup.example.net".to_string()),
+            addr: Ipv4Addr::new(192, 168, 1, 10),
         };
+        let mut serializer = TestSerializer::default();
         serializer.serialize_value(&original).unwrap();
-        let buffer = serializer.into_serializer().into_inner();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<Server>(&buffer) };
 
-        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
-        assert_eq!(output.hash_map.get(&1).unwrap(), &"a");
+        assert_eq!(output.hostname, "example.com");
+        assert_eq!(output.fallback, "backup.example.net");
+        assert_eq!(output.addr.0, [192, 168, 1, 10]);
 
-        let deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        let deserialized: Server = output.deserialize(&mut TestDeserializer).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn try_convert_errors() {
+        use super::util::{TestDeserializer, TestSerializer};
+        use crate::as_convert::AsTryConvert;
+        use rkyv::{archived_root, ser::Serializer, Deserialize};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct Route {
+            #[with(AsTryConvert<UnicastOctets>)]
+            pub gateway: Ipv4Addr,
+        }
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct RawRoute {
+            pub gateway: UnicastOctets,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let error = serializer
+            .serialize_value(&Route {
+                gateway: Ipv4Addr::new(224, 0, 0, 1),
+            })
+            .unwrap_err();
+        assert!(error.downcast_ref::<MulticastError>().is_some());
+
+        // An archive written without the check still fails to convert back
+        let mut serializer = TestSerializer::default();
+        serializer
+            .serialize_value(&RawRoute {
+                gateway: UnicastOctets([239, 1, 2, 3]),
+            })
+            .unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<Route>(&buffer) };
+        let error =
+            Deserialize::<Route, _>::deserialize(output, &mut TestDeserializer).unwrap_err();
+        assert!(error.downcast_ref::<MulticastError>().is_some());
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_convert() {
+        use super::util::{corrupt_root, TestSerializer};
+        use crate::as_convert::{AsConvert, AsTryConvert};
+        use rkyv::{check_archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct Server {
+            #[with(AsConvert<String>)]
+            pub hostname: Hostname,
+            #[with(AsTryConvert<UnicastOctets>)]
+            pub addr: Ipv4Addr,
+        }
+
+        let original = Server {
+            hostname: Hostname::from("example.com".to_string()),
+            addr: Ipv4Addr::LOCALHOST,
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = check_archived_root::<Server>(&buffer).unwrap();
+        assert_eq!(output.hostname, "example.com");
+        assert_eq!(output.addr.0, [127, 0, 0, 1]);
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedServer>(&mut corrupted);
+        assert!(check_archived_root::<Server>(&corrupted).is_err());
+    }
+}
+
+pub mod as_deltaencoded {
+    #[test]
+    fn struct_with_deltaencoded() {
+        use super::util::TestSerializer;
+        use crate::as_deltaencoded::AsDeltaEncoded;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithPostings {
+            #[with(AsDeltaEncoded)]
+            pub timestamps: Vec<u64>,
+            #[with(AsDeltaEncoded<5>)]
+            pub postings: Vec<u32>,
+            #[with(AsDeltaEncoded<1>)]
+            pub single: Vec<u32>,
+            #[with(AsDeltaEncoded)]
+            pub empty: Vec<u64>,
+        }
+
+        let original = StructWithPostings {
+            timestamps: (0..10000u64)
+                .map(|i| 1_600_000_000_000 + i * 1000 + i % 7)
+                .collect(),
+            postings: vec![2, 3, 3, 3, 10, 11, 50, 51, 52, 1000, 1000, 70000, u32::MAX],
+            single: vec![5, 8, 13],
+            empty: Vec::new(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<StructWithPostings>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.timestamps).unwrap();
+        assert!(buffer.len() < plain.len() / 3);
+
+        assert_eq!(output.timestamps.len(), 10000);
+        for (i, &timestamp) in original.timestamps.iter().enumerate() {
+            assert_eq!(output.timestamps.get(i), Some(timestamp));
+        }
+        assert_eq!(output.timestamps.get(10000), None);
+        assert!(output
+            .timestamps
+            .iter()
+            .eq(original.timestamps.iter().copied()));
+
+        for target in 0..1200 {
+            let expected = original.postings.partition_point(|&p| p < target);
+            assert_eq!(
+                output.postings.seek_ge(target),
+                original.postings.get(expected).map(|&p| (expected, p))
+            );
+            assert_eq!(
+                output.postings.contains(target),
+                original.postings.contains(&target)
+            );
+        }
+        assert_eq!(output.postings.seek_ge(70001), Some((12, u32::MAX)));
+        assert!(output.postings.contains(u32::MAX));
+        assert_eq!(output.single.seek_ge(9), Some((2, 13)));
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.seek_ge(0), None);
+        assert!(!output.empty.contains(0));
+
+        let deserialized: StructWithPostings = output.deserialize(&mut Infallible).unwrap();
         assert_eq!(deserialized, original);
     }
+
+    #[test]
+    fn deltaencoded_intersection() {
+        use super::util::TestSerializer;
+        use crate::as_deltaencoded::AsDeltaEncoded;
+        use rkyv::{archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct Postings {
+            #[with(AsDeltaEncoded<8>)]
+            pub a: Vec<u32>,
+            #[with(AsDeltaEncoded<8>)]
+            pub b: Vec<u32>,
+        }
+
+        let original = Postings {
+            a: (0..2000).map(|i| i * 3).collect(),
+            b: (0..1000).map(|i| i * 5 + 1).collect(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<Postings>(&buffer) };
+
+        // Leapfrog between the lists, skipping whole blocks whenever one falls behind
+        let mut intersection = Vec::new();
+        let mut a = output.a.iter();
+        let mut b = output.b.iter();
+        let (mut next_a, mut next_b) = (a.next(), b.next());
+        while let (Some(x), Some(y)) = (next_a, next_b) {
+            if x == y {
+                intersection.push(x);
+                next_a = a.next();
+                next_b = b.next();
+            } else if x < y {
+                next_a = a.seek_ge(y);
+            } else {
+                next_b = b.seek_ge(x);
+            }
+        }
+
+        let expected = original
+            .a
+            .iter()
+            .copied()
+            .filter(|x| original.b.contains(x))
+            .collect::<Vec<_>>();
+        assert_eq!(intersection, expected);
+    }
+
+    #[test]
+    fn deltaencoded_rejects_unsorted() {
+        use super::util::TestSerializer;
+        use crate::as_deltaencoded::{AsDeltaEncoded, UnsortedError};
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithTimestamps {
+            #[with(AsDeltaEncoded)]
+            pub timestamps: Vec<u64>,
+        }
+
+        let original = StructWithTimestamps {
+            timestamps: vec![1, 2, 2, 5, 4, 6],
+        };
+        let mut serializer = TestSerializer::default();
+        let error = serializer.serialize_value(&original).unwrap_err();
+        assert_eq!(error.downcast_ref::<UnsortedError>().unwrap().index, 4);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_deltaencoded() {
+        use super::util::{corrupt_root, TestSerializer};
+        use crate::as_deltaencoded::AsDeltaEncoded;
+        use rkyv::{check_archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithTimestamps {
+            #[with(AsDeltaEncoded<2>)]
+            pub timestamps: Vec<u64>,
+        }
+
+        let original = StructWithTimestamps {
+            timestamps: vec![0xa1b2_c3d0, 0xa1b2_c3d1, 0xa1b2_c3e0, 0xa1b2_c3e1],
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = check_archived_root::<StructWithTimestamps>(&buffer).unwrap();
+        assert_eq!(output.timestamps.get(3), Some(0xa1b2_c3e1));
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithTimestamps>(&mut corrupted);
+        assert!(check_archived_root::<StructWithTimestamps>(&corrupted).is_err());
+
+        // Moving the start of the second block below the end of the first unsorts the vector
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(8)
+            .position(|w| w == 0xa1b2_c3e0u64.to_ne_bytes())
+            .unwrap();
+        corrupted[pos..pos + 8].copy_from_slice(&0xa1b2_c3c0u64.to_ne_bytes());
+        assert!(check_archived_root::<StructWithTimestamps>(&corrupted).is_err());
+    }
+}
+
+pub mod as_f16 {
+    #[test]
+    fn struct_with_f16() {
+        use crate::as_f16::{AsBf16, AsF16};
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithHalves {
+            #[with(AsF16)]
+            pub half: f32,
+            #[with(AsF16)]
+            pub halves: Vec<f64>,
+            #[with(AsBf16)]
+            pub brain: f64,
+            #[with(AsBf16)]
+            pub brains: Vec<f32>,
+        }
+
+        let original = StructWithHalves {
+            half: -2.0,
+            halves: vec![
+                1.0,
+                65504.0,
+                65520.0,
+                f64::INFINITY,
+                -0.0,
+                2f64.powi(-24),
+                2f64.powi(-25),
+                1.5 * 2f64.powi(-25),
+                // Exactly halfway between two halves, so it rounds to the even one
+                1.0 + 2f64.powi(-11),
+                // Rounding through an `f32` first would lose the bit that breaks the tie
+                1.0 + 2f64.powi(-11) + 2f64.powi(-40),
+                f64::NAN,
+            ],
+            brain: 1.0,
+            brains: vec![f32::MAX, 3.0e38, -1.0e-40, 1.0 + 2f32.powi(-8)],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithHalves>(&buffer) };
+
+        assert_eq!(output.half.to_bits(), 0xc000);
+        assert_eq!(output.half.to_f32(), -2.0);
+        assert_eq!(
+            output
+                .halves
+                .iter()
+                .map(|half| half.to_bits())
+                .collect::<Vec<_>>(),
+            vec![
+                0x3c00, 0x7bff, 0x7c00, 0x7c00, 0x8000, 0x0001, 0x0000, 0x0001, 0x3c00, 0x3c01,
+                0x7e00
+            ]
+        );
+        assert!(output.halves[10].to_f64().is_nan());
+        assert_eq!(output.halves[5].to_f64(), 2f64.powi(-24));
+        assert!(output.halves[0] < output.halves[1]);
+        assert_eq!(output.brain.to_bits(), 0x3f80);
+        assert_eq!(
+            output
+                .brains
+                .iter()
+                .map(|brain| brain.to_bits())
+                .collect::<Vec<_>>(),
+            vec![0x7f80, 0x7f62, 0x8001, 0x3f80]
+        );
+        assert_eq!(output.brains[2].to_f32(), f32::from_bits(0x8001_0000));
+
+        let deserialized: StructWithHalves = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.half, -2.0);
+        assert_eq!(deserialized.halves[1], 65504.0);
+        assert_eq!(deserialized.halves[2], f64::INFINITY);
+        assert!(deserialized.halves[10].is_nan());
+        assert_eq!(deserialized.brains[3], 1.0);
+    }
+
+    #[test]
+    fn f16_round_trips_every_half() {
+        use crate::as_f16::AsF16;
+        use rkyv::archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithHalves {
+            #[with(AsF16)]
+            pub halves: Vec<f32>,
+        }
+
+        let values = (0..=u16::MAX)
+            .map(|bits| {
+                let sign = if bits >> 15 == 1 { -1.0 } else { 1.0 };
+                let exponent = i32::from((bits >> 10) & 0x1f);
+                let mantissa = f32::from(bits & 0x3ff);
+                match exponent {
+                    0 => sign * mantissa * 2f32.powi(-24),
+                    0x1f if mantissa == 0.0 => sign * f32::INFINITY,
+                    0x1f => f32::NAN,
+                    _ => sign * (1024.0 + mantissa) * 2f32.powi(exponent - 25),
+                }
+            })
+            .collect::<Vec<_>>();
+        let original = StructWithHalves { halves: values };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithHalves>(&buffer) };
+
+        for (bits, (half, &value)) in output.halves.iter().zip(&original.halves).enumerate() {
+            if value.is_nan() {
+                assert!(half.to_f32().is_nan());
+            } else {
+                assert_eq!(half.to_bits(), bits as u16);
+                assert_eq!(half.to_f32().to_bits(), value.to_bits());
+            }
+        }
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_f16() {
+        use crate::as_f16::{AsBf16, AsF16};
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithHalves {
+            #[with(AsF16)]
+            pub half: f32,
+            #[with(AsBf16)]
+            pub brains: Vec<f64>,
+        }
+
+        let original = StructWithHalves {
+            half: 0.5,
+            brains: vec![1.0, -3.0],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithHalves>(&buffer).unwrap();
+        assert_eq!(output.half.to_f32(), 0.5);
+        assert_eq!(output.brains[1].to_f64(), -3.0);
+    }
+}
+
+pub mod as_fixedpoint {
+    #[test]
+    fn struct_with_fixedpoint() {
+        use super::util::TestSerializer;
+        use crate::as_fixedpoint::{AsFixedPoint, FixedPoint};
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithPrices {
+            #[with(AsFixedPoint<2>)]
+            pub price: f64,
+            #[with(AsFixedPoint<2>)]
+            pub refund: f64,
+            #[with(AsFixedPoint<4>)]
+            pub rate: f64,
+            #[with(AsFixedPoint<0>)]
+            pub units: f64,
+        }
+
+        let original = StructWithPrices {
+            price: 1.15,
+            refund: -0.005,
+            rate: 0.00015,
+            units: 2.5,
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<StructWithPrices>(&buffer) };
+
+        assert_eq!(output.price.raw(), 115);
+        assert_eq!(FixedPoint::<2>::from_f64(0.145).unwrap().raw(), 15);
+        assert_eq!(FixedPoint::<2>::from_f64(-2.675).unwrap().raw(), -268);
+        assert_eq!(FixedPoint::<2>::from_f64(1.0e-300).unwrap().raw(), 0);
+        assert_eq!(
+            FixedPoint::<3>::from_f64(1.5e15).unwrap().raw(),
+            1_500_000_000_000_000_000
+        );
+        assert_eq!(output.refund.raw(), -1);
+        assert_eq!(output.rate.raw(), 2);
+        assert_eq!(output.units.raw(), 3);
+        assert_eq!(output.price.to_string(), "1.15");
+        assert_eq!(output.refund.to_string(), "-0.01");
+        assert_eq!(format!("{:?}", output.rate), "0.0002");
+        assert_eq!(output.units.to_string(), "3");
+        assert!(output.refund < output.price);
+
+        let sum = output.price.checked_add(&output.refund).unwrap();
+        assert_eq!(sum, FixedPoint::from_raw(114));
+        assert_eq!(sum.to_string(), "1.14");
+        assert_eq!(
+            output.price.checked_mul_int(-3).unwrap().to_string(),
+            "-3.45"
+        );
+        assert_eq!(output.price.checked_mul_int(i64::MAX), None);
+        assert_eq!(
+            FixedPoint::<2>::from_raw(i64::MIN).checked_sub(FixedPoint::from_raw(1)),
+            None
+        );
+        assert_eq!(
+            FixedPoint::<2>::from_raw(i64::MIN).to_string(),
+            "-92233720368547758.08"
+        );
+
+        let deserialized: StructWithPrices = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(
+            deserialized,
+            StructWithPrices {
+                price: 1.15,
+                refund: -0.01,
+                rate: 0.0002,
+                units: 3.0,
+            }
+        );
+    }
+
+    #[test]
+    fn fixedpoint_rejects_unrepresentable() {
+        use super::util::TestSerializer;
+        use crate::as_fixedpoint::{AsFixedPoint, FixedPoint, FixedPointError};
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithPrice {
+            #[with(AsFixedPoint<6>)]
+            pub price: f64,
+        }
+
+        for &(price, overflow) in &[
+            (f64::NAN, false),
+            (f64::INFINITY, true),
+            (-f64::INFINITY, true),
+            (1.0e13, true),
+            (-1.0e13, true),
+        ] {
+            let mut serializer = TestSerializer::default();
+            let error = serializer
+                .serialize_value(&StructWithPrice { price })
+                .unwrap_err();
+            match error.downcast_ref::<FixedPointError>().unwrap() {
+                FixedPointError::NotANumber => assert!(!overflow),
+                FixedPointError::Overflow => assert!(overflow),
+            }
+        }
+
+        let mut serializer = TestSerializer::default();
+        serializer
+            .serialize_value(&StructWithPrice { price: 9.0e12 })
+            .unwrap();
+        assert_eq!(
+            FixedPoint::<0>::from_f64(-9.2e18).unwrap().raw(),
+            -9_200_000_000_000_000_000
+        );
+        // `i64::MIN` as a float prints as 9.223372036854776e18, which is just out of range
+        assert!(FixedPoint::<0>::from_f64(i64::MIN as f64).is_err());
+    }
+
+    #[test]
+    fn fixedpoint_max_scale() {
+        use super::util::TestSerializer;
+        use crate::as_fixedpoint::{AsFixedPoint, FixedPoint};
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        // 18 is the largest scale that compiles, see the `compile_fail` example on `AsFixedPoint`
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithRatio {
+            #[with(AsFixedPoint<18>)]
+            pub ratio: f64,
+        }
+
+        let original = StructWithRatio { ratio: -1.5 };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<StructWithRatio>(&buffer) };
+
+        assert_eq!(output.ratio.raw(), -1_500_000_000_000_000_000);
+        assert_eq!(output.ratio.to_string(), "-1.500000000000000000");
+        assert_eq!(
+            FixedPoint::<18>::from_raw(i64::MAX).to_string(),
+            "9.223372036854775807"
+        );
+        assert!(FixedPoint::<18>::from_f64(10.0).is_err());
+
+        let deserialized: StructWithRatio = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "rust_decimal")]
+    #[test]
+    fn fixedpoint_decimal() {
+        use super::util::TestSerializer;
+        use crate::as_fixedpoint::{AsFixedPoint, FixedPoint, FixedPointError};
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+        use rust_decimal::Decimal;
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithDecimals {
+            #[with(AsFixedPoint<2>)]
+            pub price: Decimal,
+            #[with(AsFixedPoint<2>)]
+            pub tax: Decimal,
+            #[with(AsFixedPoint<2>)]
+            pub whole: Decimal,
+        }
+
+        let original = StructWithDecimals {
+            price: Decimal::new(1999, 2),
+            tax: Decimal::new(-3435, 3),
+            whole: Decimal::new(42, 0),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<StructWithDecimals>(&buffer) };
+
+        assert_eq!(output.price.raw(), 1999);
+        assert_eq!(output.tax.raw(), -344);
+        assert_eq!(output.whole.raw(), 4200);
+        assert_eq!(output.tax.to_decimal(), Decimal::new(-344, 2));
+        assert_eq!(
+            output.price.checked_add(&output.tax).unwrap().to_decimal(),
+            Decimal::new(1655, 2)
+        );
+
+        let deserialized: StructWithDecimals = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.price, original.price);
+        assert_eq!(deserialized.tax, Decimal::new(-344, 2));
+        assert_eq!(deserialized.whole, original.whole);
+
+        assert!(matches!(
+            FixedPoint::<18>::from_decimal(Decimal::new(10, 0)),
+            Err(FixedPointError::Overflow)
+        ));
+        assert_eq!(
+            FixedPoint::<18>::from_decimal(Decimal::new(9, 0))
+                .unwrap()
+                .raw(),
+            9_000_000_000_000_000_000
+        );
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_fixedpoint() {
+        use super::util::TestSerializer;
+        use crate::as_fixedpoint::AsFixedPoint;
+        use rkyv::{check_archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithPrices {
+            #[with(AsFixedPoint<3>)]
+            pub price: f64,
+            pub quantity: u32,
+        }
+
+        let original = StructWithPrices {
+            price: 12.345,
+            quantity: 7,
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = check_archived_root::<StructWithPrices>(&buffer).unwrap();
+        assert_eq!(output.price.raw(), 12345);
+        assert_eq!(
+            output.price.checked_mul_int(7).unwrap().to_string(),
+            "86.415"
+        );
+    }
+}
+
+pub mod as_frontcodedstrings {
+    #[test]
+    fn struct_with_frontcodedstrings() {
+        use crate::as_frontcodedstrings::AsFrontCodedStrings;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithUrls {
+            #[with(AsFrontCodedStrings)]
+            pub urls: Vec<String>,
+            #[with(AsFrontCodedStrings<1>)]
+            pub single: Vec<String>,
+            #[with(AsFrontCodedStrings<7>)]
+            pub empty: Vec<String>,
+        }
+
+        let mut urls = (0..10000u32)
+            .map(|i| {
+                format!(
+                    "https://example.com/{}/{}/page{}",
+                    i % 7,
+                    i.wrapping_mul(2_654_435_761) >> 20,
+                    i
+                )
+            })
+            .collect::<Vec<_>>();
+        urls.sort();
+        let original = StructWithUrls {
+            single: urls[..100].to_vec(),
+            urls,
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithUrls>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.urls).unwrap();
+        assert!(buffer.len() < plain.len() / 2);
+
+        assert_eq!(output.urls.len(), 10000);
+        for (i, url) in original.urls.iter().enumerate() {
+            assert_eq!(output.urls.get(i).as_ref(), Some(url));
+            assert_eq!(output.urls.binary_search(url), Ok(i));
+            let before = url[..url.len() - 1].to_string();
+            assert_eq!(
+                output.urls.binary_search(&before),
+                original.urls.binary_search(&before)
+            );
+            let after = format!("{}~", url);
+            assert_eq!(
+                output.urls.binary_search(&after),
+                original.urls.binary_search(&after)
+            );
+        }
+        assert_eq!(output.urls.get(10000), None);
+        assert_eq!(output.urls.binary_search(""), Err(0));
+        assert_eq!(output.urls.binary_search("~"), Err(10000));
+        assert!(output.urls.iter().eq(original.urls.iter().cloned()));
+
+        assert_eq!(output.single.get(99).as_ref(), Some(&original.single[99]));
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.iter().count(), 0);
+        assert_eq!(output.empty.binary_search("a"), Err(0));
+
+        let deserialized: StructWithUrls = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn frontcodedstrings_unicode() {
+        use crate::as_frontcodedstrings::AsFrontCodedStrings;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithStrings {
+            #[with(AsFrontCodedStrings<4>)]
+            pub strings: Vec<String>,
+        }
+
+        // "é" and "ê" share their first byte, which must not be split from the rest
+        let original = StructWithStrings {
+            strings: ["", "café", "cafê", "cafêtière", "日本", "日本語", "b", "b"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithStrings>(&buffer) };
+
+        for (i, s) in original.strings.iter().enumerate() {
+            assert_eq!(output.strings.get(i).as_ref(), Some(s));
+        }
+        let deserialized: StructWithStrings = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_frontcodedstrings() {
+        use super::util::corrupt_root;
+        use crate::as_frontcodedstrings::AsFrontCodedStrings;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithStrings {
+            #[with(AsFrontCodedStrings<4>)]
+            pub strings: Vec<String>,
+        }
+
+        let original = StructWithStrings {
+            strings: (0..10).map(|i| format!("prefix/{}/café", i)).collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithStrings>(&buffer).unwrap();
+        assert_eq!(output.strings.get(5).unwrap(), "prefix/5/café");
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithStrings>(&mut corrupted);
+        assert!(check_archived_root::<StructWithStrings>(&corrupted).is_err());
+
+        // Cutting "é" in half leaves invalid UTF-8 behind
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(2)
+            .position(|w| w == "é".as_bytes())
+            .unwrap();
+        corrupted[pos + 1] = b'x';
+        assert!(check_archived_root::<StructWithStrings>(&corrupted).is_err());
+    }
+}
+
+pub mod as_hashmap {
+    #[test]
+    fn struct_with_hashmap() {
+        use rkyv::{
+            archived_root,
+            ser::{serializers::AllocSerializer, Serializer},
+            Deserialize, Infallible,
+        };
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMap)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let mut serializer = AllocSerializer::<4096>::default();
+        let original = StructWithHashMap {
+            hash_map: vec![(1, String::from("a")), (2, String::from("b"))],
+        };
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_serializer().into_inner();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.hash_map.get(&1).unwrap(), &"a");
+
+        let deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn struct_with_checked_hashmap() {
+        use super::util::TestSerializer;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMapChecked)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithHashMap {
+            hash_map: vec![(1, String::from("a")), (2, String::from("b"))],
+        };
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.hash_map.len(), 2);
+        assert_eq!(output.hash_map.get(&1).unwrap(), &"a");
+        assert_eq!(output.hash_map.get(&2).unwrap(), &"b");
+
+        let mut deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        deserialized.hash_map.sort();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn checked_hashmap_rejects_duplicate_keys() {
+        use super::util::TestSerializer;
+        use crate::as_hashmap::DuplicateKeyError;
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMapChecked)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithHashMap {
+            hash_map: vec![
+                (1, String::from("a")),
+                (2, String::from("b")),
+                (1, String::from("c")),
+            ],
+        };
+        let error = serializer.serialize_value(&original).unwrap_err();
+        let error = error.downcast_ref::<DuplicateKeyError>().unwrap();
+        assert_eq!(error.first, 0);
+        assert_eq!(error.duplicate, 2);
+    }
+
+    #[test]
+    fn duplicate_key_error_frees_scratch() {
+        use crate::as_hashmap::{sorted_entries, unique_entries, DuplicateKeyError};
+        use rkyv::{
+            ser::{serializers::AllocScratch, ScratchSpace},
+            Fallible,
+        };
+        use std::{alloc::Layout, error::Error, ptr::NonNull};
+
+        // Counts the scratch allocations that haven't been freed yet
+        #[derive(Default)]
+        struct CountingScratch {
+            inner: AllocScratch,
+            live: usize,
+        }
+
+        impl Fallible for CountingScratch {
+            type Error = Box<dyn Error>;
+        }
+
+        impl ScratchSpace for CountingScratch {
+            unsafe fn push_scratch(
+                &mut self,
+                layout: Layout,
+            ) -> Result<NonNull<[u8]>, Self::Error> {
+                let result = self.inner.push_scratch(layout)?;
+                self.live += 1;
+                Ok(result)
+            }
+
+            unsafe fn pop_scratch(
+                &mut self,
+                ptr: NonNull<u8>,
+                layout: Layout,
+            ) -> Result<(), Self::Error> {
+                self.inner.pop_scratch(ptr, layout)?;
+                self.live -= 1;
+                Ok(())
+            }
+        }
+
+        let keys = [1, 2, 1];
+        let reject = |&mut (first, _): &mut (usize, usize), i| {
+            Err(DuplicateKeyError {
+                first,
+                duplicate: i,
+            }
+            .into())
+        };
+
+        let mut scratch = CountingScratch::default();
+        let result = unsafe { unique_entries(keys.len(), |i| &keys[i], &mut scratch, reject) };
+        assert!(result.is_err());
+        assert_eq!(scratch.live, 0);
+
+        let result = unsafe { sorted_entries(keys.len(), |i| &keys[i], &mut scratch, reject) };
+        assert!(result.is_err());
+        assert_eq!(scratch.live, 0);
+    }
+
+    #[test]
+    fn hashmap_first_wins() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMapDedup<crate::as_hashmap::FirstWins>)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: vec![
+                (1, String::from("a")),
+                (2, String::from("b")),
+                (1, String::from("c")),
+                (2, String::from("d")),
+                (3, String::from("e")),
+            ],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.hash_map.len(), 3);
+        assert_eq!(output.hash_map.get(&1).unwrap(), &"a");
+        assert_eq!(output.hash_map.get(&2).unwrap(), &"b");
+        assert_eq!(output.hash_map.get(&3).unwrap(), &"e");
+
+        let mut deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        deserialized.hash_map.sort();
+        assert_eq!(
+            deserialized.hash_map,
+            vec![
+                (1, String::from("a")),
+                (2, String::from("b")),
+                (3, String::from("e")),
+            ],
+        );
+    }
+
+    #[test]
+    fn hashmap_last_wins() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+        use std::collections::HashMap;
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMapDedup<crate::as_hashmap::LastWins>)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: vec![
+                (1, String::from("a")),
+                (2, String::from("b")),
+                (1, String::from("c")),
+                (2, String::from("d")),
+                (3, String::from("e")),
+            ],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.hash_map.len(), 3);
+        assert_eq!(output.hash_map.get(&1).unwrap(), &"c");
+        assert_eq!(output.hash_map.get(&2).unwrap(), &"d");
+        assert_eq!(output.hash_map.get(&3).unwrap(), &"e");
+
+        // Matches the behavior of collecting into a std `HashMap`
+        let expected = original.hash_map.iter().cloned().collect::<HashMap<_, _>>();
+        let deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(
+            deserialized.hash_map.into_iter().collect::<HashMap<_, _>>(),
+            expected
+        );
+    }
+
+    #[test]
+    fn hashmap_dedup_empty() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMapDedup<crate::as_hashmap::LastWins>)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert!(output.hash_map.is_empty());
+
+        let deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn hashmap_containers() {
+        use super::util::TestDeserializer;
+        use rkyv::{archived_root, Deserialize};
+        use std::{borrow::Cow, collections::VecDeque};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMaps {
+            #[with(crate::as_hashmap::AsHashMap)]
+            pub boxed: Box<[(u32, String)]>,
+            #[with(crate::as_hashmap::AsHashMap)]
+            pub array: [(u32, String); 2],
+            #[with(crate::as_hashmap::AsHashMap)]
+            pub cow: Cow<'static, [(u32, String)]>,
+            #[with(crate::as_hashmap::AsHashMapDedup<crate::as_hashmap::FirstWins>)]
+            pub deque: VecDeque<(u32, String)>,
+        }
+
+        let mut deque = VecDeque::new();
+        deque.push_back((2, String::from("g")));
+        deque.push_front((1, String::from("f")));
+        let original = StructWithHashMaps {
+            boxed: vec![(1, String::from("a"))].into_boxed_slice(),
+            array: [(1, String::from("b")), (2, String::from("c"))],
+            cow: Cow::Owned(vec![(1, String::from("d")), (2, String::from("e"))]),
+            deque,
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMaps>(&buffer) };
+        assert_eq!(output.boxed.get(&1).unwrap(), &"a");
+        assert_eq!(output.array.get(&2).unwrap(), &"c");
+        assert_eq!(output.cow.get(&1).unwrap(), &"d");
+        assert_eq!(output.deque.get(&1).unwrap(), &"f");
+        assert_eq!(output.deque.get(&2).unwrap(), &"g");
+
+        let mut deserialized: StructWithHashMaps =
+            output.deserialize(&mut TestDeserializer).unwrap();
+        deserialized.array.sort();
+        deserialized.cow.to_mut().sort();
+        deserialized.deque.make_contiguous().sort();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn hashmap_array_entry_count() {
+        use super::util::TestDeserializer;
+        use crate::as_hashmap::{AsHashMapDedup, EntryCountError, FirstWins};
+        use rkyv::{archived_root, Deserialize};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug)]
+        struct StructWithHashMap {
+            #[with(AsHashMapDedup<FirstWins>)]
+            pub array: [(u32, String); 3],
+        }
+
+        let original = StructWithHashMap {
+            array: [
+                (1, String::from("a")),
+                (2, String::from("b")),
+                (1, String::from("c")),
+            ],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.array.len(), 2);
+
+        let result: Result<StructWithHashMap, _> = output.deserialize(&mut TestDeserializer);
+        let error = result.unwrap_err();
+        let error = error.downcast_ref::<EntryCountError>().unwrap();
+        assert_eq!(error.expected, 3);
+        assert_eq!(error.found, 2);
+    }
+
+    #[cfg(feature = "smallvec")]
+    #[test]
+    fn hashmap_smallvec() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+        use smallvec::{smallvec, SmallVec};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashMap {
+            #[with(crate::as_hashmap::AsHashMap)]
+            pub hash_map: SmallVec<[(u32, u32); 4]>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: smallvec![(1, 10), (2, 20), (3, 30)],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+        assert_eq!(output.hash_map.get(&2).unwrap(), &20);
+
+        let mut deserialized: StructWithHashMap = output.deserialize(&mut Infallible).unwrap();
+        deserialized.hash_map.sort();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn hashmap_deserialize_into_maps() {
+        use crate::as_hashmap::{AsHashMap, AsHashMapDedup, LastWins};
+        use rkyv::{archived_root, with::DeserializeWith, Infallible};
+        use std::{
+            collections::{hash_map::DefaultHasher, BTreeMap, HashMap},
+            hash::BuildHasherDefault,
+        };
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        struct StructWithHashMaps {
+            #[with(AsHashMap)]
+            pub hash_map: Vec<(u32, String)>,
+            #[with(AsHashMapDedup<LastWins>)]
+            pub dedup: Vec<(u32, String)>,
+        }
+
+        let original = StructWithHashMaps {
+            hash_map: vec![(2, String::from("b")), (1, String::from("a"))],
+            dedup: vec![(1, String::from("a")), (1, String::from("b"))],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithHashMaps>(&buffer) };
+
+        let hash_map: HashMap<u32, String, BuildHasherDefault<DefaultHasher>> =
+            AsHashMap::deserialize_with(&output.hash_map, &mut Infallible).unwrap();
+        assert_eq!(hash_map.len(), 2);
+        assert_eq!(hash_map[&1], "a");
+        assert_eq!(hash_map[&2], "b");
+
+        let btree_map: BTreeMap<u32, String> =
+            AsHashMap::deserialize_with(&output.hash_map, &mut Infallible).unwrap();
+        assert_eq!(
+            btree_map.into_iter().collect::<Vec<_>>(),
+            vec![(1, String::from("a")), (2, String::from("b"))]
+        );
+
+        let dedup: HashMap<u32, String> =
+            AsHashMapDedup::<LastWins>::deserialize_with(&output.dedup, &mut Infallible).unwrap();
+        assert_eq!(dedup.len(), 1);
+        assert_eq!(dedup[&1], "b");
+    }
+
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn hashmap_deserialize_into_indexmap() {
+        use crate::as_hashmap::AsHashMap;
+        use indexmap::IndexMap;
+        use rkyv::{archived_root, with::DeserializeWith, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        struct StructWithHashMap {
+            #[with(AsHashMap)]
+            pub hash_map: Vec<(u32, String)>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: vec![(2, String::from("b")), (1, String::from("a"))],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithHashMap>(&buffer) };
+
+        let index_map: IndexMap<u32, String> =
+            AsHashMap::deserialize_with(&output.hash_map, &mut Infallible).unwrap();
+        assert_eq!(index_map.len(), 2);
+        assert_eq!(index_map[&1], "a");
+        assert_eq!(index_map[&2], "b");
+    }
+
+    #[test]
+    fn perfect_hashmap_dictionary() {
+        use super::util::TestSerializer;
+        use crate::as_hashmap::{AsPerfectHashMap, DuplicateKeyError};
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        struct Dictionary {
+            #[with(AsPerfectHashMap)]
+            pub words: Vec<(String, u32)>,
+        }
+
+        let original = Dictionary {
+            words: (0..100_000u32).map(|i| (format!("word{}", i), i)).collect(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<Dictionary>(&buffer) };
+
+        assert_eq!(output.words.len(), 100_000);
+        for i in (0..100_000u32).step_by(97) {
+            assert_eq!(output.words.get(format!("word{}", i).as_str()), Some(&i));
+        }
+        assert!(output.words.get("word100000").is_none());
+
+        let mut deserialized: Dictionary = output.deserialize(&mut Infallible).unwrap();
+        deserialized.words.sort_by_key(|&(_, i)| i);
+        assert_eq!(deserialized.words, original.words);
+
+        let duplicated = Dictionary {
+            words: vec![(String::from("a"), 0), (String::from("a"), 1)],
+        };
+        let mut serializer = TestSerializer::default();
+        let error = serializer.serialize_value(&duplicated).unwrap_err();
+        assert!(error.downcast_ref::<DuplicateKeyError>().is_some());
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_hashmap() {
+        use super::util::corrupt_root;
+        use crate::as_hashmap::{AsHashMap, AsHashMapDedup, LastWins};
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithHashMaps {
+            #[with(AsHashMap)]
+            pub hash_map: Vec<(u32, String)>,
+            #[with(AsHashMapDedup<LastWins>)]
+            pub dedup: Vec<(String, u32)>,
+        }
+
+        let original = StructWithHashMaps {
+            hash_map: vec![(1, String::from("a")), (2, String::from("b"))],
+            dedup: vec![(String::from("a"), 1), (String::from("a"), 2)],
+        };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithHashMaps>(&buffer).unwrap();
+        assert_eq!(output.hash_map.get(&1).unwrap(), &"a");
+        assert_eq!(output.dedup.get("a").unwrap(), &2);
+
+        corrupt_root::<ArchivedStructWithHashMaps>(&mut buffer);
+        assert!(check_archived_root::<StructWithHashMaps>(&buffer).is_err());
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_hashmap_rejects_duplicate_keys() {
+        use crate::as_hashmap::AsHashMap;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithHashMap {
+            #[with(AsHashMap)]
+            pub hash_map: Vec<(u32, u32)>,
+        }
+
+        let original = StructWithHashMap {
+            hash_map: vec![(0xa1b2_c3d0, 10), (0xa1b2_c3d1, 20)],
+        };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        assert!(check_archived_root::<StructWithHashMap>(&buffer).is_ok());
+
+        // Repeated keys can't be serialized, so overwrite one key with the other instead
+        let key = 0xa1b2_c3d1u32.to_ne_bytes();
+        let pos = buffer.windows(4).position(|w| w == key).unwrap();
+        buffer[pos..pos + 4].copy_from_slice(&0xa1b2_c3d0u32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithHashMap>(&buffer).is_err());
+    }
+}
+
+pub mod as_hashset {
+    #[test]
+    fn struct_with_hashset() {
+        use rkyv::{
+            archived_root,
+            ser::{serializers::AllocSerializer, Serializer},
+            Deserialize, Infallible,
+        };
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashSet {
+            #[with(crate::as_hashset::AsHashSet)]
+            pub hash_set: Vec<u32>,
+        }
+
+        let mut serializer = AllocSerializer::<4096>::default();
+        let original = StructWithHashSet {
+            hash_set: vec![3, 1, 4, 5, 9, 2, 6],
+        };
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_serializer().into_inner();
+
+        let output = unsafe { archived_root::<StructWithHashSet>(&buffer) };
+        assert_eq!(output.hash_set.len(), 7);
+        for x in original.hash_set.iter() {
+            assert!(output.hash_set.contains(x));
+        }
+        assert!(!output.hash_set.contains(&7));
+
+        let mut deserialized: StructWithHashSet = output.deserialize(&mut Infallible).unwrap();
+        deserialized.hash_set.sort();
+        assert_eq!(deserialized.hash_set, vec![1, 2, 3, 4, 5, 6, 9]);
+    }
+
+    #[test]
+    fn struct_with_checked_hashset() {
+        use super::util::TestSerializer;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithHashSet {
+            #[with(crate::as_hashset::AsHashSetChecked)]
+            pub hash_set: Vec<String>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithHashSet {
+            hash_set: vec![String::from("a"), String::from("b")],
+        };
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = unsafe { archived_root::<StructWithHashSet>(&buffer) };
+        assert_eq!(output.hash_set.len(), 2);
+        assert!(output.hash_set.contains("a"));
+        assert!(output.hash_set.contains("b"));
+
+        let mut deserialized: StructWithHashSet = output.deserialize(&mut Infallible).unwrap();
+        deserialized.hash_set.sort();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn checked_hashset_rejects_duplicate_elements() {
+        use super::util::TestSerializer;
+        use crate::as_hashmap::DuplicateKeyError;
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithHashSet {
+            #[with(crate::as_hashset::AsHashSetChecked)]
+            pub hash_set: Vec<String>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithHashSet {
+            hash_set: vec![String::from("a"), String::from("b"), String::from("b")],
+        };
+        let error = serializer.serialize_value(&original).unwrap_err();
+        let error = error.downcast_ref::<DuplicateKeyError>().unwrap();
+        assert_eq!(error.first, 1);
+        assert_eq!(error.duplicate, 2);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_hashset() {
+        use super::util::corrupt_root;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithHashSet {
+            #[with(crate::as_hashset::AsHashSet)]
+            pub hash_set: Vec<String>,
+        }
+
+        let original = StructWithHashSet {
+            hash_set: vec![String::from("a"), String::from("b")],
+        };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithHashSet>(&buffer).unwrap();
+        assert!(output.hash_set.contains("a"));
+
+        corrupt_root::<ArchivedStructWithHashSet>(&mut buffer);
+        assert!(check_archived_root::<StructWithHashSet>(&buffer).is_err());
+    }
+}
+
+pub mod as_indexmap {
+    #[test]
+    fn struct_with_indexmap() {
+        use super::util::TestSerializer;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithIndexMap {
+            #[with(crate::as_indexmap::AsIndexMap)]
+            pub index_map: Vec<(String, u32)>,
+        }
+
+        let original = StructWithIndexMap {
+            index_map: (0..100u32)
+                .rev()
+                .map(|i| (format!("key{}", i * 37 % 100), i))
+                .collect(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = unsafe { archived_root::<StructWithIndexMap>(&buffer) };
+        assert_eq!(output.index_map.len(), 100);
+        for (i, (k, v)) in original.index_map.iter().enumerate() {
+            assert_eq!(output.index_map.get(k.as_str()), Some(v));
+            assert_eq!(output.index_map.get_index_of(k.as_str()), Some(i));
+            let (key, value) = output.index_map.get_index(i).unwrap();
+            assert_eq!((key.as_str(), value), (k.as_str(), v));
+        }
+        assert!(output.index_map.get("missing").is_none());
+        assert!(output.index_map.get_index(100).is_none());
+        assert!(output
+            .index_map
+            .iter()
+            .map(|(k, v)| (k.as_str(), *v))
+            .eq(original.index_map.iter().map(|(k, v)| (k.as_str(), *v))));
+
+        let deserialized: StructWithIndexMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn indexmap_empty() {
+        use super::util::TestSerializer;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithIndexMap {
+            #[with(crate::as_indexmap::AsIndexMap)]
+            pub index_map: Vec<(u32, u32)>,
+        }
+
+        let original = StructWithIndexMap {
+            index_map: Vec::new(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = unsafe { archived_root::<StructWithIndexMap>(&buffer) };
+        assert!(output.index_map.is_empty());
+        assert!(output.index_map.get(&0).is_none());
+
+        let deserialized: StructWithIndexMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn indexmap_duplicate_keys() {
+        use super::util::TestSerializer;
+        use crate::{
+            as_hashmap::{DuplicateKeyError, FirstWins, RejectDuplicates},
+            as_indexmap::AsIndexMap,
+        };
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithIndexMaps {
+            #[with(AsIndexMap)]
+            pub last_wins: Vec<(u32, char)>,
+            #[with(AsIndexMap<FirstWins>)]
+            pub first_wins: Vec<(u32, char)>,
+        }
+
+        let entries = vec![(2, 'a'), (1, 'b'), (2, 'c'), (3, 'd'), (1, 'e')];
+        let original = StructWithIndexMaps {
+            last_wins: entries.clone(),
+            first_wins: entries,
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = unsafe { archived_root::<StructWithIndexMaps>(&buffer) };
+        assert_eq!(output.last_wins.len(), 3);
+        assert!(output.last_wins.iter().map(|(k, v)| (*k, *v)).eq(vec![
+            (2, 'c'),
+            (1, 'e'),
+            (3, 'd')
+        ]));
+        assert_eq!(output.last_wins.get_full(&1), Some((1, &1, &'e')));
+        assert!(output.first_wins.iter().map(|(k, v)| (*k, *v)).eq(vec![
+            (2, 'a'),
+            (1, 'b'),
+            (3, 'd')
+        ]));
+        assert_eq!(output.first_wins.get_full(&3), Some((2, &3, &'d')));
+
+        let deserialized: StructWithIndexMaps = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.last_wins, vec![(2, 'c'), (1, 'e'), (3, 'd')]);
+        assert_eq!(deserialized.first_wins, vec![(2, 'a'), (1, 'b'), (3, 'd')]);
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithCheckedIndexMap {
+            #[with(AsIndexMap<RejectDuplicates>)]
+            pub index_map: Vec<(u32, char)>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let error = serializer
+            .serialize_value(&StructWithCheckedIndexMap {
+                index_map: vec![(1, 'a'), (2, 'b'), (1, 'c')],
+            })
+            .unwrap_err();
+        let error = error.downcast_ref::<DuplicateKeyError>().unwrap();
+        assert_eq!((error.first, error.duplicate), (0, 2));
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_indexmap() {
+        use super::util::{corrupt_root, TestSerializer};
+        use rkyv::{check_archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithIndexMap {
+            #[with(crate::as_indexmap::AsIndexMap)]
+            pub index_map: Vec<(u32, u32)>,
+        }
+
+        let original = StructWithIndexMap {
+            index_map: (0..10u32).map(|i| (0xa1b2_c3d0 + i, i)).collect(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = check_archived_root::<StructWithIndexMap>(&buffer).unwrap();
+        assert_eq!(output.index_map.get(&0xa1b2_c3d5).unwrap(), &5);
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithIndexMap>(&mut corrupted);
+        assert!(check_archived_root::<StructWithIndexMap>(&corrupted).is_err());
+
+        // The ordered entries are serialized before the index, so the first occurrence of a key is
+        // its entry. Changing it leaves the entry unreachable through the index.
+        let mut corrupted = buffer.clone();
+        let key = 0xa1b2_c3d3u32.to_ne_bytes();
+        let pos = corrupted.windows(4).position(|w| w == key).unwrap();
+        corrupted[pos..pos + 4].copy_from_slice(&0xdead_beefu32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithIndexMap>(&corrupted).is_err());
+    }
+}
+
+pub mod as_internedstrings {
+    #[test]
+    fn struct_with_internedstrings() {
+        use crate::as_internedstrings::AsInternedStrings;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithStrings {
+            #[with(AsInternedStrings)]
+            pub countries: Vec<String>,
+            #[with(AsInternedStrings)]
+            pub statuses: Vec<String>,
+            #[with(AsInternedStrings)]
+            pub empty: Vec<String>,
+        }
+
+        let countries = ["US", "FR", "DE", "JP", "BR"];
+        let statuses = ["awaiting payment", "awaiting shipment", "delivered", ""];
+        let original = StructWithStrings {
+            countries: (0..1000)
+                .map(|i| countries[i * 7 % countries.len()].to_string())
+                .collect(),
+            statuses: (0..1000)
+                .map(|i| statuses[i * 3 % statuses.len()].to_string())
+                .collect(),
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithStrings>(&buffer) };
+
+        let plain =
+            rkyv::to_bytes::<_, 4096>(&(original.countries.clone(), original.statuses.clone()))
+                .unwrap();
+        assert!(buffer.len() < plain.len() / 2);
+
+        assert_eq!(output.countries.len(), 1000);
+        assert_eq!(output.countries.strings().len(), 5);
+        assert_eq!(output.statuses.strings().len(), 4);
+        assert_eq!(output.statuses.strings()[3].as_str(), "awaiting shipment");
+        for (i, status) in original.statuses.iter().enumerate() {
+            assert_eq!(output.statuses.get(i), Some(status.as_str()));
+        }
+        assert_eq!(output.statuses.get(1000), None);
+        assert!(output.countries.iter().eq(original.countries.iter()));
+        assert!(output
+            .countries
+            .iter()
+            .rev()
+            .eq(original.countries.iter().rev()));
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.iter().count(), 0);
+
+        let deserialized: StructWithStrings = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn shared_internedstrings() {
+        use crate::as_internedstrings::{
+            AsInternedStrings, AsSharedInternedStrings, InterningSerializer,
+        };
+        use rkyv::{
+            archived_root,
+            ser::{serializers::AllocSerializer, Serializer},
+            Deserialize, Infallible,
+        };
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct Local {
+            #[with(AsInternedStrings)]
+            pub open: Vec<String>,
+            #[with(AsInternedStrings)]
+            pub closed: Vec<String>,
+        }
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct Shared {
+            #[with(AsSharedInternedStrings)]
+            pub open: Vec<String>,
+            #[with(AsSharedInternedStrings)]
+            pub closed: Vec<String>,
+        }
+
+        let statuses = (0..20)
+            .map(|i| format!("a rather long status name {}", i))
+            .collect::<Vec<_>>();
+        let open = statuses[..15].to_vec();
+        let closed = statuses[5..].iter().rev().cloned().collect::<Vec<_>>();
+
+        let local = rkyv::to_bytes::<_, 4096>(&Local {
+            open: open.clone(),
+            closed: closed.clone(),
+        })
+        .unwrap();
+
+        let original = Shared { open, closed };
+        let mut serializer = InterningSerializer::new(AllocSerializer::<4096>::default());
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner().into_serializer().into_inner();
+        let output = unsafe { archived_root::<Shared>(&buffer) };
+
+        assert!(buffer.len() < local.len());
+        assert_eq!(
+            output.open.strings()[10].as_ptr(),
+            output.closed.strings()[9].as_ptr()
+        );
+        assert!(output.closed.iter().eq(original.closed.iter()));
+
+        let deserialized: Shared = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_internedstrings() {
+        use super::util::corrupt_root;
+        use crate::as_internedstrings::{AsSharedInternedStrings, InterningSerializer};
+        use rkyv::{
+            check_archived_root,
+            ser::{serializers::AllocSerializer, Serializer},
+        };
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithStrings {
+            #[with(AsSharedInternedStrings)]
+            pub first: Vec<String>,
+            #[with(AsSharedInternedStrings)]
+            pub second: Vec<String>,
+        }
+
+        let long = String::from("a string too long to be inline");
+        let original = StructWithStrings {
+            first: vec![String::from("short"), long.clone()],
+            second: vec![long; 8],
+        };
+        let mut serializer = InterningSerializer::new(AllocSerializer::<4096>::default());
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner().into_serializer().into_inner();
+
+        let output = check_archived_root::<StructWithStrings>(&buffer).unwrap();
+        assert_eq!(output.second.get(7), Some("a string too long to be inline"));
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithStrings>(&mut corrupted);
+        assert!(check_archived_root::<StructWithStrings>(&corrupted).is_err());
+
+        // The eight zero indices of `second` are the last thing written before the root
+        let mut corrupted = buffer.clone();
+        let pos = corrupted.windows(32).rposition(|w| w == [0; 32]).unwrap();
+        corrupted[pos..pos + 4].copy_from_slice(&1u32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithStrings>(&corrupted).is_err());
+    }
+}
+
+pub mod as_keyedmap {
+    use crate::as_keyedmap::KeyFn;
+
+    #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+    #[cfg_attr(feature = "validation", archive(check_bytes))]
+    pub struct User {
+        pub id: u64,
+        pub name: String,
+    }
+
+    pub struct ById;
+
+    impl KeyFn<User> for ById {
+        type Key = u64;
+
+        fn key(user: &User) -> &u64 {
+            &user.id
+        }
+    }
+
+    pub struct ByName;
+
+    impl KeyFn<User> for ByName {
+        type Key = String;
+
+        fn key(user: &User) -> &String {
+            &user.name
+        }
+    }
+
+    fn users(len: u64) -> Vec<User> {
+        (0..len)
+            .map(|id| User {
+                id,
+                name: format!("user{}", id),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn struct_with_keyedmap() {
+        use crate::as_keyedmap::AsKeyedMap;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithKeyedMaps {
+            #[with(AsKeyedMap<ById>)]
+            pub by_id: Vec<User>,
+            #[with(AsKeyedMap<ByName>)]
+            pub by_name: Vec<User>,
+        }
+
+        let original = StructWithKeyedMaps {
+            by_id: users(1000),
+            by_name: users(10),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithKeyedMaps>(&buffer) };
+
+        assert_eq!(output.by_id.len(), 1000);
+        for id in 0..1000 {
+            assert_eq!(output.by_id.get(&id).unwrap().id, id);
+        }
+        assert!(output.by_id.get(&1000).is_none());
+        assert_eq!(output.by_name.get("user3").unwrap().id, 3);
+        assert!(output.by_name.get("user10").is_none());
+
+        let mut deserialized: StructWithKeyedMaps = output.deserialize(&mut Infallible).unwrap();
+        deserialized.by_id.sort_by_key(|user| user.id);
+        deserialized.by_name.sort_by_key(|user| user.id);
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn keyedmap_empty() {
+        use crate::as_keyedmap::AsKeyedMap;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithKeyedMap {
+            #[with(AsKeyedMap<ById>)]
+            pub users: Vec<User>,
+        }
+
+        let original = StructWithKeyedMap { users: Vec::new() };
+        let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithKeyedMap>(&buffer) };
+        assert!(output.users.is_empty());
+        let deserialized: StructWithKeyedMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_keyedmap() {
+        use super::util::corrupt_root;
+        use crate::as_keyedmap::AsKeyedMap;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithKeyedMap {
+            #[with(AsKeyedMap<ById>)]
+            pub users: Vec<User>,
+        }
+
+        let original = StructWithKeyedMap { users: users(10) };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithKeyedMap>(&buffer).unwrap();
+        assert_eq!(output.users.get(&4).unwrap().name, "user4");
+
+        corrupt_root::<ArchivedStructWithKeyedMap>(&mut buffer);
+        assert!(check_archived_root::<StructWithKeyedMap>(&buffer).is_err());
+    }
+}
+
+pub mod as_multimap {
+    #[test]
+    fn struct_with_multimap() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithMultiMap {
+            #[with(crate::as_multimap::AsMultiMap)]
+            pub tags: Vec<(String, u32)>,
+        }
+
+        let original = StructWithMultiMap {
+            tags: vec![
+                (String::from("red"), 4),
+                (String::from("blue"), 2),
+                (String::from("red"), 1),
+                (String::from("green"), 5),
+                (String::from("red"), 3),
+                (String::from("blue"), 6),
+            ],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithMultiMap>(&buffer) };
+        assert_eq!(output.tags.len(), 3);
+        assert_eq!(output.tags.get("red").unwrap().as_slice(), &[4, 1, 3]);
+        assert_eq!(output.tags.get("blue").unwrap().as_slice(), &[2, 6]);
+        assert_eq!(output.tags.get("green").unwrap().as_slice(), &[5]);
+        assert!(output.tags.get("yellow").is_none());
+
+        let deserialized: StructWithMultiMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.tags.len(), original.tags.len());
+        for key in ["red", "blue", "green"].iter() {
+            let values = |tags: &Vec<(String, u32)>| {
+                tags.iter()
+                    .filter(|(k, _)| k == key)
+                    .map(|(_, v)| *v)
+                    .collect::<Vec<_>>()
+            };
+            assert_eq!(values(&deserialized.tags), values(&original.tags));
+        }
+    }
+
+    #[test]
+    fn multimap_empty() {
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithMultiMap {
+            #[with(crate::as_multimap::AsMultiMap)]
+            pub tags: Vec<(String, u32)>,
+        }
+
+        let original = StructWithMultiMap { tags: Vec::new() };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = unsafe { archived_root::<StructWithMultiMap>(&buffer) };
+        assert!(output.tags.is_empty());
+
+        let deserialized: StructWithMultiMap = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_multimap() {
+        use super::util::corrupt_root;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithMultiMap {
+            #[with(crate::as_multimap::AsMultiMap)]
+            pub tags: Vec<(String, u32)>,
+        }
+
+        let original = StructWithMultiMap {
+            tags: vec![
+                (String::from("red"), 1),
+                (String::from("blue"), 2),
+                (String::from("red"), 3),
+            ],
+        };
+        let mut buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithMultiMap>(&buffer).unwrap();
+        assert_eq!(output.tags.get("red").unwrap().as_slice(), &[1, 3]);
+
+        corrupt_root::<ArchivedStructWithMultiMap>(&mut buffer);
+        assert!(check_archived_root::<StructWithMultiMap>(&buffer).is_err());
+    }
+}
+
+pub mod as_quantizedu8 {
+    #[test]
+    fn struct_with_quantizedu8() {
+        use super::util::TestSerializer;
+        use crate::as_quantizedu8::AsQuantizedU8;
+        use rkyv::{archived_root, ser::Serializer, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithTelemetry {
+            #[with(AsQuantizedU8)]
+            pub readings: Vec<f64>,
+            #[with(AsQuantizedU8)]
+            pub constant: Vec<f32>,
+            #[with(AsQuantizedU8)]
+            pub extremes: Vec<f64>,
+            #[with(AsQuantizedU8)]
+            pub empty: Vec<f32>,
+        }
+
+        let original = StructWithTelemetry {
+            readings: (0..1000)
+                .map(|i| (f64::from(i) * 0.1).sin() * 50.0)
+                .collect(),
+            constant: vec![0.25; 10],
+            extremes: vec![f64::MAX, -f64::MAX, 0.0],
+            empty: Vec::new(),
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+        let output = unsafe { archived_root::<StructWithTelemetry>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.readings).unwrap();
+        assert!(buffer.len() < plain.len() / 7);
+
+        let readings = &output.readings;
+        assert_eq!(readings.len(), 1000);
+        assert_eq!(readings.quantized().len(), 1000);
+        let min = original
+            .readings
+            .iter()
+            .copied()
+            .fold(f64::INFINITY, f64::min);
+        let max = original
+            .readings
+            .iter()
+            .copied()
+            .fold(f64::NEG_INFINITY, f64::max);
+        assert_eq!(readings.offset(), min);
+        assert!((readings.offset() + 255.0 * readings.scale() - max).abs() < 1e-9);
+        for (i, &reading) in original.readings.iter().enumerate() {
+            let decoded = readings.get(i).unwrap();
+            assert!((decoded - reading).abs() <= readings.scale() / 2.0 + 1e-9);
+        }
+        assert_eq!(readings.get(1000), None);
+        assert_eq!(readings.iter().len(), 1000);
+        assert_eq!(readings.iter().next_back(), readings.get(999));
+
+        assert!(output.constant.iter().eq(original.constant.iter().copied()));
+        assert_eq!(output.constant.scale(), 0.0);
+        assert_eq!(output.extremes.quantized()[..2], [255, 0]);
+        assert!(output.extremes.iter().all(f64::is_finite));
+        assert!(output.empty.is_empty());
+
+        let deserialized: StructWithTelemetry = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.readings.len(), 1000);
+        assert_eq!(deserialized.constant, original.constant);
+        assert!(deserialized.empty.is_empty());
+    }
+
+    #[test]
+    fn quantizedu8_rejects_non_finite() {
+        use super::util::TestSerializer;
+        use crate::as_quantizedu8::{AsQuantizedU8, NonFiniteError};
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithTelemetry {
+            #[with(AsQuantizedU8)]
+            pub readings: Vec<f32>,
+        }
+
+        let original = StructWithTelemetry {
+            readings: vec![1.0, 2.0, f32::NEG_INFINITY, f32::NAN],
+        };
+        let mut serializer = TestSerializer::default();
+        let error = serializer.serialize_value(&original).unwrap_err();
+        assert_eq!(error.downcast_ref::<NonFiniteError>().unwrap().index, 2);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_quantizedu8() {
+        use super::util::{corrupt_root, TestSerializer};
+        use crate::as_quantizedu8::AsQuantizedU8;
+        use rkyv::{check_archived_root, ser::Serializer};
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithTelemetry {
+            #[with(AsQuantizedU8)]
+            pub readings: Vec<f64>,
+        }
+
+        let original = StructWithTelemetry {
+            readings: vec![-1.5, 0.0, 1001.25],
+        };
+        let mut serializer = TestSerializer::default();
+        serializer.serialize_value(&original).unwrap();
+        let buffer = serializer.into_inner();
+
+        let output = check_archived_root::<StructWithTelemetry>(&buffer).unwrap();
+        assert_eq!(output.readings.get(0), Some(-1.5));
+        let scale = output.readings.scale();
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithTelemetry>(&mut corrupted);
+        assert!(check_archived_root::<StructWithTelemetry>(&corrupted).is_err());
+
+        // A NaN scale would decode every float to NaN
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(8)
+            .position(|w| w == scale.to_ne_bytes())
+            .unwrap();
+        corrupted[pos..pos + 8].copy_from_slice(&f64::NAN.to_ne_bytes());
+        assert!(check_archived_root::<StructWithTelemetry>(&corrupted).is_err());
+    }
+}
+
+pub mod as_runlength {
+    #[test]
+    fn struct_with_runlength() {
+        use crate::as_runlength::AsRunLength;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithRuns {
+            #[with(AsRunLength)]
+            pub readings: Vec<u32>,
+            #[with(AsRunLength)]
+            pub states: Vec<String>,
+            #[with(AsRunLength)]
+            pub distinct: Vec<u8>,
+            #[with(AsRunLength)]
+            pub empty: Vec<u32>,
+        }
+
+        let original = StructWithRuns {
+            readings: (0..10000u32).map(|i| i / 1000 * 7).collect(),
+            states: ["idle", "idle", "running", "idle", "idle", "idle"]
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+            distinct: vec![1, 2, 3],
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithRuns>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.readings).unwrap();
+        assert!(buffer.len() < plain.len() / 100);
+
+        assert_eq!(output.readings.len(), 10000);
+        assert_eq!(output.readings.run_count(), 10);
+        for (i, reading) in original.readings.iter().enumerate() {
+            assert_eq!(output.readings.get(i), Some(reading));
+        }
+        assert_eq!(output.readings.get(10000), None);
+        assert!(output.readings.iter().eq(original.readings.iter()));
+        assert_eq!(output.readings.iter().len(), 10000);
+
+        assert_eq!(output.states.values(), ["idle", "running", "idle"]);
+        assert!(output
+            .states
+            .runs()
+            .map(|(state, run)| (state.as_str(), run))
+            .eq([("idle", 2), ("running", 1), ("idle", 3)].iter().copied()));
+        assert_eq!(output.states.get(2).unwrap(), "running");
+        assert_eq!(output.distinct.run_count(), 3);
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.len(), 0);
+        assert_eq!(output.empty.get(0), None);
+        assert_eq!(output.empty.iter().count(), 0);
+
+        let deserialized: StructWithRuns = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_runlength() {
+        use super::util::corrupt_root;
+        use crate::as_runlength::AsRunLength;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithRuns {
+            #[with(AsRunLength)]
+            pub states: Vec<String>,
+        }
+
+        let original = StructWithRuns {
+            states: [vec!["a long idle state"; 0x0a1b], vec!["running"; 0x0c1d]]
+                .concat()
+                .iter()
+                .map(|s| s.to_string())
+                .collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithRuns>(&buffer).unwrap();
+        assert_eq!(output.states.get(0x0a1b).unwrap(), "running");
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithRuns>(&mut corrupted);
+        assert!(check_archived_root::<StructWithRuns>(&corrupted).is_err());
+
+        // Ending the first run at zero makes it empty
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(4)
+            .position(|w| w == 0x0a1bu32.to_ne_bytes())
+            .unwrap();
+        corrupted[pos..pos + 4].copy_from_slice(&0u32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithRuns>(&corrupted).is_err());
+    }
+}
+
+pub mod as_sortedvec {
+    #[test]
+    fn struct_with_sortedvec() {
+        use crate::as_sortedvec::AsSortedVec;
+        use rkyv::{archived_root, Deserialize, Infallible};
+        use std::ops::Bound;
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithSortedVec {
+            #[with(AsSortedVec)]
+            pub sorted: Vec<(u32, String)>,
+        }
+
+        // Multiplying by an odd number permutes the keys
+        let original = StructWithSortedVec {
+            sorted: (0..10000u32)
+                .map(|i| {
+                    let key = i.wrapping_mul(7919) % 10000 * 2;
+                    (key, key.to_string())
+                })
+                .collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithSortedVec>(&buffer) };
+
+        assert_eq!(output.sorted.len(), 10000);
+        for i in 0..10000 {
+            assert_eq!(output.sorted.get(&(i * 2)).unwrap(), &(i * 2).to_string());
+            assert!(!output.sorted.contains_key(&(i * 2 + 1)));
+        }
+        assert_eq!(output.sorted.lower_bound(&0), 0);
+        assert_eq!(output.sorted.lower_bound(&7), 4);
+        assert_eq!(output.sorted.lower_bound(&8), 4);
+        assert_eq!(output.sorted.upper_bound(&8), 5);
+        assert_eq!(output.sorted.lower_bound(&20000), 10000);
+
+        let keys =
+            |iter: crate::as_sortedvec::Iter<'_, u32, _>| iter.map(|(k, _)| *k).collect::<Vec<_>>();
+        assert_eq!(keys(output.sorted.range(3..9)), vec![4, 6, 8]);
+        assert_eq!(keys(output.sorted.range(4..=8)), vec![4, 6, 8]);
+        assert_eq!(keys(output.sorted.range(..4)), vec![0, 2]);
+        assert_eq!(keys(output.sorted.range(19995..)), vec![19996, 19998]);
+        assert_eq!(keys(output.sorted.range(5..6)), Vec::<u32>::new());
+        let decreasing = (Bound::Included(9), Bound::Excluded(3));
+        assert_eq!(keys(output.sorted.range(decreasing)), Vec::<u32>::new());
+        assert_eq!(output.sorted.range(..).len(), 10000);
+
+        let deserialized: StructWithSortedVec = output.deserialize(&mut Infallible).unwrap();
+        let mut expected = original.sorted.clone();
+        expected.sort();
+        assert_eq!(deserialized.sorted, expected);
+    }
+
+    #[test]
+    fn sortedvec_string_keys() {
+        use crate::as_sortedvec::AsSortedVec;
+        use rkyv::archived_root;
+        use std::ops::Bound;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithSortedVec {
+            #[with(AsSortedVec)]
+            pub sorted: Vec<(String, u32)>,
+        }
+
+        let original = StructWithSortedVec {
+            sorted: ["pear", "apple", "fig", "banana", "cherry"]
+                .iter()
+                .enumerate()
+                .map(|(i, k)| (k.to_string(), i as u32))
+                .collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithSortedVec>(&buffer) };
+
+        assert_eq!(output.sorted.get("fig"), Some(&2));
+        assert!(output.sorted.get("grape").is_none());
+        let range = output
+            .sorted
+            .range::<str, _>((Bound::Included("b"), Bound::Excluded("d")))
+            .map(|(k, _)| k.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(range, vec!["banana", "cherry"]);
+    }
+
+    #[test]
+    fn sortedvec_duplicate_keys() {
+        use crate::{
+            as_hashmap::{FirstWins, LastWins},
+            as_sortedvec::AsSortedVec,
+        };
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize)]
+        struct StructWithSortedVecs {
+            #[with(AsSortedVec<FirstWins>)]
+            pub first: Vec<(u32, u32)>,
+            #[with(AsSortedVec<LastWins>)]
+            pub last: Vec<(u32, u32)>,
+        }
+
+        let entries = vec![(2, 0), (1, 1), (2, 2), (1, 3), (2, 4)];
+        let original = StructWithSortedVecs {
+            first: entries.clone(),
+            last: entries,
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithSortedVecs>(&buffer) };
+
+        let deserialized: StructWithSortedVecs = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.first, vec![(1, 1), (2, 0)]);
+        assert_eq!(deserialized.last, vec![(1, 3), (2, 4)]);
+    }
+
+    #[test]
+    fn sortedvec_rejects_duplicate_keys() {
+        use super::util::TestSerializer;
+        use crate::as_hashmap::{DuplicateKeyError, RejectDuplicates};
+        use rkyv::ser::Serializer;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithSortedVec {
+            #[with(crate::as_sortedvec::AsSortedVec<RejectDuplicates>)]
+            pub sorted: Vec<(u32, String)>,
+        }
+
+        let mut serializer = TestSerializer::default();
+        let original = StructWithSortedVec {
+            sorted: vec![
+                (3, String::from("a")),
+                (1, String::from("b")),
+                (3, String::from("c")),
+            ],
+        };
+        let error = serializer.serialize_value(&original).unwrap_err();
+        let error = error.downcast_ref::<DuplicateKeyError>().unwrap();
+        assert_eq!(error.first, 0);
+        assert_eq!(error.duplicate, 2);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_sortedvec() {
+        use super::util::corrupt_root;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithSortedVec {
+            #[with(crate::as_sortedvec::AsSortedVec)]
+            pub sorted: Vec<(u32, u32)>,
+        }
+
+        let original = StructWithSortedVec {
+            sorted: (0..10u32).rev().map(|i| (0xa1b2_c3d0 + i, i)).collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithSortedVec>(&buffer).unwrap();
+        assert_eq!(output.sorted.get(&0xa1b2_c3d5).unwrap(), &5);
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithSortedVec>(&mut corrupted);
+        assert!(check_archived_root::<StructWithSortedVec>(&corrupted).is_err());
+
+        // Moving a key past the one after it leaves the entries out of order
+        let mut corrupted = buffer.clone();
+        let key = 0xa1b2_c3d3u32.to_ne_bytes();
+        let pos = corrupted.windows(4).position(|w| w == key).unwrap();
+        corrupted[pos..pos + 4].copy_from_slice(&0xa1b2_c3d8u32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithSortedVec>(&corrupted).is_err());
+    }
+}
+
+pub mod as_sparsevec {
+    #[test]
+    fn struct_with_sparsevec() {
+        use crate::as_sparsevec::AsSparseVec;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct StructWithSparse {
+            #[with(AsSparseVec)]
+            pub embedding: Vec<f32>,
+            #[with(AsSparseVec)]
+            pub labels: Vec<String>,
+            #[with(AsSparseVec)]
+            pub zeros: Vec<u64>,
+            #[with(AsSparseVec)]
+            pub empty: Vec<u64>,
+        }
+
+        let original = StructWithSparse {
+            embedding: (0..10000)
+                .map(|i| if i % 20 == 3 { i as f32 * 0.25 } else { 0.0 })
+                .collect(),
+            labels: vec![
+                String::new(),
+                String::from("cat"),
+                String::new(),
+                String::new(),
+                String::from("dog"),
+            ],
+            zeros: vec![0; 100],
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithSparse>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.embedding).unwrap();
+        assert!(buffer.len() < plain.len() / 5);
+
+        assert_eq!(output.embedding.len(), 10000);
+        assert_eq!(output.embedding.stored_len(), 500);
+        for (i, &value) in original.embedding.iter().enumerate() {
+            assert_eq!(output.embedding.get(i), Some(value));
+        }
+        assert_eq!(output.embedding.get(10000), None);
+        assert_eq!(output.embedding.get_stored(3), Some(&0.75));
+        assert_eq!(output.embedding.get_stored(4), None);
+        assert_eq!(
+            output.embedding.iter_sparse().next_back(),
+            Some((9983, &(9983.0 * 0.25)))
+        );
+
+        assert_eq!(output.labels.len(), 5);
+        assert_eq!(output.labels.values(), ["cat", "dog"]);
+        assert!(output
+            .labels
+            .iter_sparse()
+            .map(|(i, label)| (i, label.as_str()))
+            .eq([(1, "cat"), (4, "dog")].iter().copied()));
+        assert_eq!(output.labels.get_stored(2), None);
+        assert_eq!(output.zeros.len(), 100);
+        assert_eq!(output.zeros.stored_len(), 0);
+        assert_eq!(output.zeros.get(99), Some(0));
+        assert!(output.empty.is_empty());
+
+        let deserialized: StructWithSparse = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_sparsevec() {
+        use super::util::corrupt_root;
+        use crate::as_sparsevec::AsSparseVec;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithSparse {
+            #[with(AsSparseVec)]
+            pub values: Vec<u32>,
+        }
+
+        let mut values = vec![0; 0x1000];
+        values[0x0a1b] = 1;
+        values[0x0c1d] = 2;
+        let original = StructWithSparse { values };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithSparse>(&buffer).unwrap();
+        assert_eq!(output.values.get(0x0c1d), Some(2));
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithSparse>(&mut corrupted);
+        assert!(check_archived_root::<StructWithSparse>(&corrupted).is_err());
+
+        // Moving the second index before the first puts them out of order
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(4)
+            .position(|w| w == 0x0c1du32.to_ne_bytes())
+            .unwrap();
+        corrupted[pos..pos + 4].copy_from_slice(&0x0a1au32.to_ne_bytes());
+        assert!(check_archived_root::<StructWithSparse>(&corrupted).is_err());
+    }
+}
+
+pub mod as_string {
+    use std::{fmt, num::ParseIntError, str::FromStr};
+
+    /// An order id that formats as `ORD-` followed by its number
+    #[derive(Debug, PartialEq)]
+    pub struct OrderId(pub u64);
+
+    impl fmt::Display for OrderId {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "ORD-{:08}", self.0)
+        }
+    }
+
+    impl FromStr for OrderId {
+        type Err = ParseIntError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            s.trim_start_matches("ORD-").parse().map(OrderId)
+        }
+    }
+
+    #[test]
+    fn struct_with_string() {
+        use super::util::TestDeserializer;
+        use crate::as_string::AsString;
+        use rkyv::{archived_root, Deserialize};
+        use std::net::{IpAddr, Ipv6Addr};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct Order {
+            #[with(AsString)]
+            pub id: OrderId,
+            #[with(AsString)]
+            pub client: IpAddr,
+            #[with(AsString)]
+            pub quantity: u16,
+            #[with(AsString)]
+            pub note: String,
+        }
+
+        let original = Order {
+            id: OrderId(1234),
+            client: IpAddr::V6(Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+            quantity: 7,
+            note: "leave at the door".to_string(),
+        };
+        let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+        let output = unsafe { archived_root::<Order>(&buffer) };
+
+        assert_eq!(output.id, "ORD-00001234");
+        assert_eq!(output.id.parse().unwrap(), OrderId(1234));
+        assert_eq!(output.client.as_str(), "2001:db8::1");
+        assert!(output.client.parse().unwrap().is_ipv6());
+        assert_eq!(output.quantity.parse(), Ok(7));
+        assert_eq!(output.quantity.len(), 1);
+        assert!(output.note.starts_with("leave"));
+        assert_eq!(output.note.as_archived_string(), "leave at the door");
+        assert_eq!(format!("{:?}", output.id), "\"ORD-00001234\"");
+
+        let deserialized: Order = output.deserialize(&mut TestDeserializer).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[test]
+    fn string_parse_errors() {
+        use super::util::TestDeserializer;
+        use crate::as_string::AsString;
+        use rkyv::{archived_root, Deserialize};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq)]
+        struct Order {
+            #[with(AsString)]
+            pub id: OrderId,
+        }
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct RawOrder {
+            pub id: String,
+        }
+
+        // Strings written by something else might not parse
+        let raw = RawOrder {
+            id: "ORD-12ab".to_string(),
+        };
+        let buffer = rkyv::to_bytes::<_, 256>(&raw).unwrap();
+        let output = unsafe { archived_root::<Order>(&buffer) };
+
+        assert_eq!(output.id, "ORD-12ab");
+        assert!(output.id.parse().is_err());
+        let error =
+            Deserialize::<Order, _>::deserialize(output, &mut TestDeserializer).unwrap_err();
+        assert!(error.downcast_ref::<ParseIntError>().is_some());
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_string() {
+        use super::util::corrupt_root;
+        use crate::as_string::AsString;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct Order {
+            #[with(AsString)]
+            pub id: OrderId,
+            pub quantity: u32,
+        }
+
+        let original = Order {
+            id: OrderId(42),
+            quantity: 3,
+        };
+        let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+
+        let output = check_archived_root::<Order>(&buffer).unwrap();
+        assert_eq!(output.id, "ORD-00000042");
+        assert_eq!(output.quantity, 3);
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedOrder>(&mut corrupted);
+        assert!(check_archived_root::<Order>(&corrupted).is_err());
+
+        // Invalid UTF-8 in the string
+        let mut corrupted = buffer.clone();
+        let pos = corrupted.windows(4).position(|w| w == b"ORD-").unwrap();
+        corrupted[pos] = 0xff;
+        assert!(check_archived_root::<Order>(&corrupted).is_err());
+    }
+}
+
+pub mod as_trie {
+    #[test]
+    fn struct_with_trie() {
+        use crate::as_trie::AsTrie;
+        use rkyv::{archived_root, Deserialize, Infallible};
+        use std::collections::BTreeMap;
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithTrie {
+            #[with(AsTrie)]
+            pub words: Vec<(String, u32)>,
+        }
+
+        // Hex strings of scrambled numbers share plenty of prefixes of different lengths
+        let words = (0..5000u32)
+            .map(|i| (format!("{:x}", i.wrapping_mul(2_654_435_761) >> 12), i))
+            .collect::<Vec<_>>();
+        let expected = words.iter().cloned().collect::<BTreeMap<_, _>>();
+        let original = StructWithTrie { words };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithTrie>(&buffer) };
+
+        assert_eq!(output.words.len(), expected.len());
+        for (key, value) in expected.iter() {
+            assert_eq!(output.words.get(key), Some(value));
+            assert!(!output.words.contains_key(&format!("{}g", key)));
+        }
+
+        for i in 0..0x1000u32 {
+            let prefix = format!("{:x}", i);
+            let found = output
+                .words
+                .starts_with(&prefix)
+                .map(|(k, v)| (k.to_string(), *v))
+                .collect::<Vec<_>>();
+            let wanted = expected
+                .range(prefix.clone()..)
+                .take_while(|(k, _)| k.starts_with(&prefix))
+                .map(|(k, v)| (k.clone(), *v))
+                .collect::<Vec<_>>();
+            assert_eq!(found, wanted);
+
+            let query = format!("{}{}", prefix, prefix);
+            let longest = (0..=query.len())
+                .rev()
+                .find_map(|end| expected.get_key_value(&query[..end]))
+                .map(|(k, v)| (k.as_str(), v));
+            assert_eq!(output.words.longest_prefix(&query), longest);
+        }
+        assert_eq!(output.words.starts_with("").len(), expected.len());
+
+        let deserialized: StructWithTrie = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized.words, expected.into_iter().collect::<Vec<_>>());
+    }
+
+    #[test]
+    fn trie_edge_cases() {
+        use crate::{as_hashmap::FirstWins, as_trie::AsTrie};
+        use rkyv::archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        struct StructWithTries {
+            #[with(AsTrie)]
+            pub empty: Vec<(String, u32)>,
+            #[with(AsTrie)]
+            pub last: Vec<(String, u32)>,
+            #[with(AsTrie<FirstWins>)]
+            pub first: Vec<(String, u32)>,
+        }
+
+        let words = vec![
+            (String::from(""), 0),
+            (String::from("añejo"), 1),
+            (String::from("año"), 2),
+            (String::from("a"), 3),
+            (String::from("año"), 4),
+        ];
+        let original = StructWithTries {
+            empty: Vec::new(),
+            last: words.clone(),
+            first: words,
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithTries>(&buffer) };
+
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.get(""), None);
+        assert_eq!(output.empty.starts_with("").len(), 0);
+        assert_eq!(output.empty.longest_prefix("abc"), None);
+
+        assert_eq!(output.last.len(), 4);
+        assert_eq!(output.last.get(""), Some(&0));
+        assert_eq!(output.last.get("año"), Some(&4));
+        assert_eq!(output.first.get("año"), Some(&2));
+        assert_eq!(output.last.get("añ"), None);
+        assert_eq!(
+            output
+                .last
+                .starts_with("añ")
+                .map(|(k, _)| k)
+                .collect::<Vec<_>>(),
+            vec!["añejo", "año"]
+        );
+        assert_eq!(output.last.longest_prefix("b"), Some(("", &0)));
+        assert_eq!(output.last.longest_prefix("añox"), Some(("año", &4)));
+        assert_eq!(output.last.longest_prefix("añe"), Some(("a", &3)));
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_trie() {
+        use super::util::corrupt_root;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithTrie {
+            #[with(crate::as_trie::AsTrie)]
+            pub words: Vec<(String, u32)>,
+        }
+
+        let original = StructWithTrie {
+            words: (0..10u32).map(|i| (format!("key{:05}", i), i)).collect(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithTrie>(&buffer).unwrap();
+        assert_eq!(output.words.get("key00005"), Some(&5));
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithTrie>(&mut corrupted);
+        assert!(check_archived_root::<StructWithTrie>(&corrupted).is_err());
+
+        // Short strings are stored inline, so this overwrites the key of an entry
+        let mut corrupted = buffer.clone();
+        let pos = corrupted.windows(8).position(|w| w == b"key00003").unwrap();
+        corrupted[pos..pos + 8].copy_from_slice(b"key00009");
+        assert!(check_archived_root::<StructWithTrie>(&corrupted).is_err());
+    }
+}
+
+pub mod as_varintvec {
+    #[test]
+    fn struct_with_varintvec() {
+        use crate::as_varintvec::AsVarIntVec;
+        use rkyv::{archived_root, Deserialize, Infallible};
+
+        #[derive(rkyv::Archive, rkyv::Serialize, rkyv::Deserialize, Debug, PartialEq, Eq)]
+        struct StructWithInts {
+            #[with(AsVarIntVec)]
+            pub ids: Vec<u64>,
+            #[with(AsVarIntVec<1>)]
+            pub small: Vec<u32>,
+            #[with(AsVarIntVec<5>)]
+            pub signed: Vec<i64>,
+            #[with(AsVarIntVec)]
+            pub empty: Vec<u64>,
+        }
+
+        let original = StructWithInts {
+            ids: (0..10000u64).map(|i| i * i % 1000).collect(),
+            small: vec![0, 127, 128, 16383, 16384, u32::MAX],
+            signed: vec![0, -1, 1, -64, 64, i64::MIN, i64::MAX, -1_000_000],
+            empty: Vec::new(),
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+        let output = unsafe { archived_root::<StructWithInts>(&buffer) };
+
+        let plain = rkyv::to_bytes::<_, 4096>(&original.ids).unwrap();
+        assert!(buffer.len() < plain.len() / 3);
+
+        assert_eq!(output.ids.len(), 10000);
+        assert_eq!(output.ids.stride(), 32);
+        for (i, &id) in original.ids.iter().enumerate() {
+            assert_eq!(output.ids.get(i), Some(id));
+        }
+        assert_eq!(output.ids.get(10000), None);
+        assert!(output.ids.iter().eq(original.ids.iter().copied()));
+
+        assert_eq!(output.small.encoded_len(), 1 + 1 + 2 + 2 + 3 + 5);
+        assert!(output.small.iter().eq(original.small.iter().copied()));
+        // Zigzag encoding keeps small negative values small
+        assert_eq!(output.signed.get(3), Some(-64));
+        assert_eq!(output.signed.get(5), Some(i64::MIN));
+        assert!(output.signed.iter().eq(original.signed.iter().copied()));
+        assert!(output.empty.is_empty());
+        assert_eq!(output.empty.iter().count(), 0);
+
+        let deserialized: StructWithInts = output.deserialize(&mut Infallible).unwrap();
+        assert_eq!(deserialized, original);
+    }
+
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_varintvec() {
+        use super::util::corrupt_root;
+        use crate::as_varintvec::AsVarIntVec;
+        use rkyv::check_archived_root;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithInts {
+            #[with(AsVarIntVec<2>)]
+            pub values: Vec<u16>,
+        }
+
+        let original = StructWithInts {
+            values: vec![1, 2, 3, 4, 5, 0x7ffe],
+        };
+        let buffer = rkyv::to_bytes::<_, 4096>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithInts>(&buffer).unwrap();
+        assert_eq!(output.values.get(5), Some(0x7ffe));
+
+        let mut corrupted = buffer.clone();
+        corrupt_root::<ArchivedStructWithInts>(&mut corrupted);
+        assert!(check_archived_root::<StructWithInts>(&corrupted).is_err());
+
+        // 0x7ffe is encoded as [0xfe, 0xff, 0x01], and 0x1fffe doesn't fit in a `u16`
+        let mut corrupted = buffer.clone();
+        let pos = corrupted
+            .windows(3)
+            .position(|w| w == [0xfe, 0xff, 0x01])
+            .unwrap();
+        corrupted[pos + 2] = 0x07;
+        assert!(check_archived_root::<StructWithInts>(&corrupted).is_err());
+
+        // Continuing the last integer past the end of the data truncates it
+        let mut corrupted = buffer.clone();
+        corrupted[pos + 2] = 0x81;
+        assert!(check_archived_root::<StructWithInts>(&corrupted).is_err());
+    }
+}
+
+pub mod custom_phantom {
+    #[cfg(feature = "validation")]
+    #[test]
+    fn validate_custom_phantom() {
+        use crate::custom_phantom::CustomPhantom;
+        use rkyv::check_archived_root;
+        use std::marker::PhantomData;
+
+        #[derive(rkyv::Archive, rkyv::Serialize)]
+        #[archive(check_bytes)]
+        struct StructWithPhantom {
+            pub num: i32,
+            #[with(CustomPhantom<u64>)]
+            pub phantom: PhantomData<String>,
+        }
+
+        let original = StructWithPhantom {
+            num: 42,
+            phantom: PhantomData,
+        };
+        let buffer = rkyv::to_bytes::<_, 256>(&original).unwrap();
+
+        let output = check_archived_root::<StructWithPhantom>(&buffer).unwrap();
+        assert_eq!(output.num, 42);
+    }
 }
@@ -0,0 +1,42 @@
+//! LEB128 variable-length integers used by the encoded wrappers.
+
+/// The largest number of bytes a `u64` can take up when encoded.
+pub(crate) const MAX_LEN: usize = 10;
+
+/// Returns the number of bytes `value` takes up when encoded.
+#[inline]
+pub(crate) fn encoded_len(value: u64) -> usize {
+    let bits = 64 - (value | 1).leading_zeros() as usize;
+    (bits + 6) / 7
+}
+
+/// Encodes `value`, passing each byte to `push` in order.
+#[inline]
+pub(crate) fn encode(mut value: u64, mut push: impl FnMut(u8)) {
+    while value >= 0x80 {
+        push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    push(value as u8);
+}
+
+/// Decodes a value starting at `*pos` and advances `*pos` past it.
+///
+/// Returns `None` if the bytes end before the value does or the value does not fit in a `u64`.
+#[inline]
+pub(crate) fn decode(bytes: &[u8], pos: &mut usize) -> Option<u64> {
+    let mut value = 0u64;
+    for i in 0..MAX_LEN {
+        let byte = *bytes.get(*pos + i)?;
+        let bits = u64::from(byte & 0x7f);
+        if i == MAX_LEN - 1 && bits > 1 {
+            return None;
+        }
+        value |= bits << (7 * i);
+        if byte & 0x80 == 0 {
+            *pos += i + 1;
+            return Some(value);
+        }
+    }
+    None
+}
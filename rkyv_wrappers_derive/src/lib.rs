@@ -0,0 +1,301 @@
+//! Derive macros for [rkyv_wrappers](https://docs.rs/rkyv_wrappers).
+//!
+//! These macros are re-exported by `rkyv_wrappers` next to the traits they implement when its
+//! `derive` feature is enabled, so they should be used through that crate.
+
+#![deny(missing_docs)]
+
+use proc_macro2::TokenStream;
+use quote::{format_ident, quote};
+use syn::{parse_macro_input, spanned::Spanned, Data, DeriveInput, Error, Fields};
+
+/// Derives `Columnar` for a struct so that vectors of it can be archived with `AsColumnar`.
+///
+/// For a struct named `Row`, this generates:
+///
+/// - `ArchivedRowColumns`: the archived columns, with one public `ArchivedVec` per field and
+///   `len`, `is_empty`, `row`, and `rows` methods.
+/// - `ArchivedRowRef<'a>`: a view of a single row, with one public reference per field.
+/// - `RowColumnsResolver`: the resolver for the columns.
+///
+/// Each field type must implement `Archive`. Generic structs and field wrappers are not
+/// supported.
+///
+/// Add `#[columnar(check_bytes)]` to the struct to also implement `CheckBytes` for the archived
+/// columns. This requires the `validation` feature of `rkyv_wrappers`.
+///
+/// See `rkyv_wrappers::as_columnar` for details.
+#[proc_macro_derive(Columnar, attributes(columnar))]
+pub fn derive_columnar(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    match derive_columnar_impl(&input) {
+        Ok(result) => result.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+fn parse_check_bytes(input: &DeriveInput) -> Result<bool, Error> {
+    let mut check_bytes = false;
+    for attr in input.attrs.iter().filter(|a| a.path().is_ident("columnar")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("check_bytes") {
+                check_bytes = true;
+                Ok(())
+            } else {
+                Err(meta.error("unrecognized columnar argument"))
+            }
+        })?;
+    }
+    Ok(check_bytes)
+}
+
+fn derive_columnar_impl(input: &DeriveInput) -> Result<TokenStream, Error> {
+    let check_bytes = parse_check_bytes(input)?;
+
+    if !input.generics.params.is_empty() {
+        return Err(Error::new_spanned(
+            &input.generics,
+            "Columnar cannot be derived for generic structs",
+        ));
+    }
+
+    let fields = match &input.data {
+        Data::Struct(data) => match &data.fields {
+            Fields::Named(fields) => &fields.named,
+            _ => {
+                return Err(Error::new(
+                    input.span(),
+                    "Columnar can only be derived for structs with named fields",
+                ))
+            }
+        },
+        _ => {
+            return Err(Error::new(
+                input.span(),
+                "Columnar can only be derived for structs",
+            ))
+        }
+    };
+    if fields.is_empty() {
+        return Err(Error::new(
+            input.span(),
+            "Columnar cannot be derived for structs without fields",
+        ));
+    }
+
+    let vis = &input.vis;
+    let name = &input.ident;
+    let columns = format_ident!("Archived{}Columns", name);
+    let row = format_ident!("Archived{}Ref", name);
+    let resolver = format_ident!("{}ColumnsResolver", name);
+
+    let names = fields
+        .iter()
+        .map(|f| f.ident.as_ref().unwrap())
+        .collect::<Vec<_>>();
+    let name_strs = names.iter().map(|n| n.to_string()).collect::<Vec<_>>();
+    let tys = fields.iter().map(|f| &f.ty).collect::<Vec<_>>();
+    // Prefixed so that fields named like the parameters of `check_bytes` don't shadow them
+    let locals = names
+        .iter()
+        .map(|n| format_ident!("__{}", n))
+        .collect::<Vec<_>>();
+    let first = names[0];
+    let first_local = &locals[0];
+    let rest_locals = &locals[1..];
+    let rest_name_strs = &name_strs[1..];
+
+    let columns_doc = format!("The archived columns of a vector of [`{}`].", name);
+    let row_doc = format!("A row of an [`{}`].", columns);
+    let resolver_doc = format!("The resolver for an [`{}`].", columns);
+    let column_docs = names
+        .iter()
+        .map(|n| format!("The `{}` column.", n))
+        .collect::<Vec<_>>();
+    let row_field_docs = names
+        .iter()
+        .map(|n| format!("The `{}` field of the row.", n))
+        .collect::<Vec<_>>();
+
+    let check_bytes_impl = if check_bytes {
+        quote! {
+            const _: () = {
+                use ::rkyv::{
+                    bytecheck::CheckBytes, validation::ArchiveContext, vec::ArchivedVec, Archived,
+                };
+                use ::rkyv_wrappers::as_columnar::ColumnarError;
+                use ::std::{boxed::Box, error::Error, ptr};
+
+                impl<__C> CheckBytes<__C> for #columns
+                where
+                    __C: ArchiveContext + ?Sized,
+                    __C::Error: Error,
+                    #(Archived<#tys>: CheckBytes<__C>,)*
+                    #(<Archived<#tys> as CheckBytes<__C>>::Error: Error + 'static,)*
+                {
+                    type Error = ColumnarError;
+
+                    unsafe fn check_bytes<'__a>(
+                        value: *const Self,
+                        context: &mut __C,
+                    ) -> Result<&'__a Self, Self::Error> {
+                        #(
+                            let #locals = ArchivedVec::<Archived<#tys>>::check_bytes(
+                                ptr::addr_of!((*value).#names),
+                                context,
+                            )
+                            .map_err(|e| ColumnarError::ColumnError {
+                                column: #name_strs,
+                                error: Box::new(e),
+                            })?;
+                        )*
+                        #(
+                            if #rest_locals.len() != #first_local.len() {
+                                return Err(ColumnarError::LengthMismatch {
+                                    column: #rest_name_strs,
+                                    expected: #first_local.len(),
+                                    actual: #rest_locals.len(),
+                                });
+                            }
+                        )*
+                        Ok(&*value)
+                    }
+                }
+            };
+        }
+    } else {
+        quote! {}
+    };
+
+    Ok(quote! {
+        #[doc = #columns_doc]
+        #[repr(C)]
+        #vis struct #columns {
+            #(
+                #[doc = #column_docs]
+                pub #names: ::rkyv::vec::ArchivedVec<::rkyv::Archived<#tys>>,
+            )*
+        }
+
+        #[doc = #row_doc]
+        #[derive(Clone, Copy)]
+        #vis struct #row<'a> {
+            #(
+                #[doc = #row_field_docs]
+                pub #names: &'a ::rkyv::Archived<#tys>,
+            )*
+        }
+
+        #[doc = #resolver_doc]
+        #vis struct #resolver {
+            #(#names: ::rkyv::vec::VecResolver,)*
+        }
+
+        impl #columns {
+            /// Gets the number of rows.
+            #[inline]
+            pub fn len(&self) -> usize {
+                self.#first.len()
+            }
+
+            /// Returns whether there are no rows.
+            #[inline]
+            pub fn is_empty(&self) -> bool {
+                self.#first.is_empty()
+            }
+
+            /// Gets the row at the given index.
+            #[inline]
+            pub fn row(&self, index: usize) -> Option<#row<'_>> {
+                if index < self.len() {
+                    Some(#row {
+                        #(#names: &self.#names[index],)*
+                    })
+                } else {
+                    None
+                }
+            }
+
+            /// Gets an iterator over the rows.
+            #[inline]
+            pub fn rows(
+                &self,
+            ) -> impl DoubleEndedIterator<Item = #row<'_>> + ExactSizeIterator + '_ {
+                (0..self.len()).map(move |index| #row {
+                    #(#names: &self.#names[index],)*
+                })
+            }
+        }
+
+        impl ::rkyv_wrappers::as_columnar::Columnar for #name {
+            type Columns = #columns;
+            type Resolver = #resolver;
+
+            #[inline]
+            unsafe fn resolve_columns(
+                rows: &[Self],
+                pos: usize,
+                resolver: Self::Resolver,
+                out: *mut Self::Columns,
+            ) {
+                #(
+                    let (fp, fo) = ::rkyv::out_field!(out.#names);
+                    ::rkyv::vec::ArchivedVec::resolve_from_len(
+                        rows.len(),
+                        pos + fp,
+                        resolver.#names,
+                        fo,
+                    );
+                )*
+            }
+        }
+
+        impl<__S> ::rkyv_wrappers::as_columnar::SerializeColumnar<__S> for #name
+        where
+            __S: ::rkyv::ser::ScratchSpace + ::rkyv::ser::Serializer + ?Sized,
+            #(#tys: ::rkyv::Serialize<__S>,)*
+        {
+            #[inline]
+            fn serialize_columns(
+                rows: &[Self],
+                serializer: &mut __S,
+            ) -> Result<Self::Resolver, __S::Error> {
+                Ok(#resolver {
+                    #(
+                        #names: ::rkyv::vec::ArchivedVec::serialize_from_iter::<#tys, _, _, _>(
+                            rows.iter().map(|row| &row.#names),
+                            serializer,
+                        )?,
+                    )*
+                })
+            }
+        }
+
+        impl<__D> ::rkyv_wrappers::as_columnar::DeserializeColumnar<__D> for #name
+        where
+            __D: ::rkyv::Fallible + ?Sized,
+            #(::rkyv::Archived<#tys>: ::rkyv::Deserialize<#tys, __D>,)*
+        {
+            #[inline]
+            fn deserialize_columns(
+                columns: &Self::Columns,
+                deserializer: &mut __D,
+            ) -> Result<::std::vec::Vec<Self>, __D::Error> {
+                let mut result = ::std::vec::Vec::with_capacity(columns.len());
+                for index in 0..columns.len() {
+                    result.push(#name {
+                        #(
+                            #names: ::rkyv::Deserialize::<#tys, __D>::deserialize(
+                                &columns.#names[index],
+                                deserializer,
+                            )?,
+                        )*
+                    });
+                }
+                Ok(result)
+            }
+        }
+
+        #check_bytes_impl
+    })
+}